@@ -0,0 +1,73 @@
+/*
+Throughput of every effect, in samples per second, on one second of audio at 44.1 and 96 kHz,
+mono and stereo. Each effect is built with its default settings, as the command line uses it.
+Criterion compares every run with the previous one and reports the change; to check a change
+against a fixed reference, save one first and compare with it afterwards:
+    cargo bench --bench effects -- --save-baseline before
+    cargo bench --bench effects -- --baseline before
+Slowdowns beyond the noise threshold are reported as "Performance has regressed".
+ */
+use std::hint::black_box;
+use std::time::Duration;
+
+use audiofxrs::dsp::WhiteNoise;
+use audiofxrs::effects::vocoder::Carrier;
+use audiofxrs::effects::{
+    GainEffect, GranularEffect, PanEffect, ParallelMix, PitchShiftEffect, ResampleEffect, ReverbEffect, StereoWidthEffect,
+    TapeEffect, TimeStretchEffect, VocoderEffect,
+};
+use audiofxrs::{AudioData, AudioEffect};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const SAMPLE_RATES: [u32; 2] = [44100, 96000];
+const CHANNEL_COUNTS: [usize; 2] = [1, 2];
+const NOISE_SEED: u32 = 0xBE4C_0001;
+
+// One second of white noise at half scale
+fn test_input(sample_rate: u32, num_channels: usize) -> AudioData {
+    let mut noise = WhiteNoise::new(NOISE_SEED);
+    let samples = (0..sample_rate as usize * num_channels).map(|_| 0.5 * noise.next_sample()).collect();
+    AudioData::new(samples, sample_rate, num_channels)
+}
+
+fn bench_effect<E: AudioEffect>(c: &mut Criterion, name: &str, mut make_effect: impl FnMut() -> E) {
+    let mut group = c.benchmark_group(name);
+    group.sample_size(10).measurement_time(Duration::from_secs(3));
+    for sample_rate in SAMPLE_RATES {
+        for num_channels in CHANNEL_COUNTS {
+            let input = test_input(sample_rate, num_channels);
+            group.throughput(Throughput::Elements(input.samples.len() as u64));
+            let id = BenchmarkId::from_parameter(format!("{}Hz-{}ch", sample_rate, num_channels));
+            group.bench_with_input(id, &input, |b, input| {
+                let mut effect = make_effect();
+                b.iter(|| effect.process(black_box(input)));
+            });
+        }
+    }
+    group.finish();
+}
+
+fn effects(c: &mut Criterion) {
+    bench_effect(c, "gain", GainEffect::new);
+    bench_effect(c, "pan", PanEffect::new);
+    bench_effect(c, "stereo-width", StereoWidthEffect::new);
+    bench_effect(c, "reverb", ReverbEffect::new);
+    bench_effect(c, "tape", TapeEffect::new);
+    bench_effect(c, "granular", GranularEffect::new);
+    bench_effect(c, "vocoder", || VocoderEffect::new(Carrier::Saw(110.0)));
+    bench_effect(c, "resample", || ResampleEffect::new(48000));
+    bench_effect(c, "pitch-shift", || {
+        let mut pitch_shift = PitchShiftEffect::new();
+        pitch_shift.set_semitones(7.0);
+        pitch_shift
+    });
+    bench_effect(c, "time-stretch", || {
+        let mut time_stretch = TimeStretchEffect::new();
+        time_stretch.set_stretch(1.5);
+        time_stretch
+    });
+    bench_effect(c, "parallel-mix", || ParallelMix::new(TapeEffect::new(), 0.5));
+}
+
+criterion_group!(benches, effects);
+criterion_main!(benches);
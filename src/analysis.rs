@@ -0,0 +1,602 @@
+/*
+Analysis commands inspect an input file instead of processing it.
+
+Tempo estimation works on an onset strength envelope: the signal is cut into short frames,
+the log energy of each frame is computed, and only the increases in energy are kept (onsets).
+The envelope is then autocorrelated, and the lag with the strongest periodicity inside a
+plausible tempo range is turned into beats per minute. A log-Gaussian weighting centred on
+120 BPM keeps the estimate from jumping to half or double tempo.
+
+Pitch tracking uses the YIN algorithm on overlapping frames: the cumulative mean normalized
+difference function dips at the fundamental period, and the first dip below a threshold is
+taken as the period of voiced frames.
+
+Key estimation builds a chroma profile by measuring the energy of every semitone over several
+octaves, folds it into 12 pitch classes and correlates it with the Krumhansl-Kessler major and
+minor key profiles for all 24 keys.
+
+Loudness is measured on all channels with `dsp::loudness` (EBU R128: integrated loudness,
+loudness range and true peak).
+
+The spectrum is taken with the STFT in `dsp::stft` (Hann window, 75 % overlap). Averaging the
+power of every frame gives a long-term spectrum, scaled so a full-scale sine reads 0 dBFS; its
+strongest peaks and centroid are printed, and it or the per-frame spectrogram can be exported as
+CSV for plotting.
+ */
+use std::fmt::Write;
+use std::fs;
+
+use audiofxrs::audio_io::AudioData;
+use audiofxrs::dsp::goertzel::goertzel_power;
+use audiofxrs::dsp::loudness::measure_loudness;
+use audiofxrs::dsp::stft::Stft;
+
+use crate::process;
+
+const FRAME_SIZE: usize = 1024;
+const HOP_SIZE: usize = 512;
+const MIN_BPM: f32 = 60.0;
+const MAX_BPM: f32 = 200.0;
+const PREFERRED_BPM: f32 = 120.0;
+const TEMPO_PRIOR_WIDTH: f32 = 1.0; // In octaves
+
+const PITCH_WINDOW: usize = 1024;
+const PITCH_HOP: usize = 1024;
+const MIN_PITCH_HZ: f32 = 50.0;
+const MAX_PITCH_HZ: f32 = 1000.0;
+const YIN_THRESHOLD: f32 = 0.15;
+const SILENCE_RMS: f32 = 0.001;
+
+const CHROMA_FRAME_SIZE: usize = 8192;
+const CHROMA_LOWEST_MIDI_NOTE: u32 = 48; // C3
+const CHROMA_OCTAVES: u32 = 4;
+
+const SPECTRUM_FRAME_SIZE: usize = 4096;
+const SPECTRUM_HOP_SIZE: usize = 1024;
+const SPECTRUM_PEAKS: usize = 5;
+const SPECTRUM_FLOOR_DB: f32 = -200.0;
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+const MAJOR_PROFILE: [f32; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR_PROFILE: [f32; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// A musical key, identified by its tonic pitch class (0 = C) and mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    pub tonic: usize,
+    pub minor: bool,
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mode = if self.minor { "minor" } else { "major" };
+        write!(f, "{} {}", NOTE_NAMES[self.tonic], mode)
+    }
+}
+
+pub const USAGE: &str =
+    "[--bpm] [--pitch] [--key] [--loudness] [--spectrum] [--spectrum-csv <file>] [--spectrogram-csv <file>]";
+
+pub fn run(args: &[String]) {
+    let mut estimate_tempo = false;
+    let mut track_pitch = false;
+    let mut detect_key = false;
+    let mut measure = false;
+    let mut spectrum = false;
+    let mut spectrum_csv = None;
+    let mut spectrogram_csv = None;
+    let mut input_file = None;
+
+    let mut options = args[2..].iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--bpm" => estimate_tempo = true,
+            "--pitch" => track_pitch = true,
+            "--key" => detect_key = true,
+            "--loudness" => measure = true,
+            "--spectrum" => spectrum = true,
+            "--spectrum-csv" => spectrum_csv = Some(options.next().expect("Missing file name after --spectrum-csv")),
+            "--spectrogram-csv" => {
+                spectrogram_csv = Some(options.next().expect("Missing file name after --spectrogram-csv"))
+            }
+            _ if arg.starts_with("--") => {
+                eprintln!("Unknown analysis option: {}", arg);
+                std::process::exit(1);
+            }
+            _ => input_file = Some(arg),
+        }
+    }
+
+    let input_file = match input_file {
+        Some(input_file)
+            if estimate_tempo
+                || track_pitch
+                || detect_key
+                || measure
+                || spectrum
+                || spectrum_csv.is_some()
+                || spectrogram_csv.is_some() =>
+        {
+            input_file
+        }
+        _ => {
+            eprintln!("Usage: {} analyze {} <input.wav>", args[0], USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    // Loudness is measured per channel, everything else on the mono downmix
+    let audio = process::read_or_exit(input_file, None);
+    let samples = downmix(&audio);
+    let sample_rate = audio.sample_rate;
+
+    if estimate_tempo {
+        match estimate_bpm(&samples, sample_rate) {
+            Some(bpm) => println!("Tempo: {:.1} BPM", bpm),
+            None => println!("Tempo: not enough audio to estimate"),
+        }
+    }
+
+    if track_pitch {
+        let track = pitch_track(&samples, sample_rate);
+        let mut voiced: Vec<f32> = track.iter().flatten().copied().collect();
+        if voiced.is_empty() {
+            println!("Pitch: no pitched frames found");
+        } else {
+            voiced.sort_by(|a, b| a.total_cmp(b));
+            let median = voiced[voiced.len() / 2];
+            println!(
+                "Pitch: {:.1} Hz ({}), range {:.1}-{:.1} Hz, voiced in {:.0}% of frames",
+                median,
+                note_name(median),
+                voiced[0],
+                voiced[voiced.len() - 1],
+                100.0 * voiced.len() as f32 / track.len() as f32
+            );
+        }
+    }
+
+    if detect_key {
+        match estimate_key(&samples, sample_rate) {
+            Some(key) => println!("Key: {}", key),
+            None => println!("Key: not enough tonal content to estimate"),
+        }
+    }
+
+    if measure {
+        let loudness = measure_loudness(&audio.samples, audio.num_channels, audio.sample_rate);
+        println!(
+            "Loudness: {:.1} LUFS integrated, {:.1} LU range, {:.1} dBTP true peak",
+            loudness.integrated_lufs, loudness.range_lu, loudness.true_peak_dbtp
+        );
+    }
+
+    if spectrum || spectrum_csv.is_some() {
+        let levels = average_spectrum(&samples);
+        let bin_width = sample_rate as f32 / SPECTRUM_FRAME_SIZE as f32;
+
+        if spectrum {
+            let peaks: Vec<String> = spectrum_peaks(&levels, SPECTRUM_PEAKS)
+                .into_iter()
+                .map(|bin| format!("{:.1} Hz ({:.1} dB)", bin as f32 * bin_width, levels[bin]))
+                .collect();
+            if peaks.is_empty() {
+                println!("Spectrum: no content");
+            } else {
+                println!(
+                    "Spectrum: peaks at {}, centroid {:.1} Hz",
+                    peaks.join(", "),
+                    spectral_centroid(&levels) * bin_width
+                );
+            }
+        }
+
+        if let Some(csv_file) = spectrum_csv {
+            let mut csv = String::from("frequency_hz,level_db\n");
+            for (bin, level) in levels.iter().enumerate() {
+                let _ = writeln!(csv, "{:.2},{:.2}", bin as f32 * bin_width, level);
+            }
+            fs::write(csv_file, csv).expect("Failed to write spectrum CSV file");
+            println!("Spectrum written to {}", csv_file);
+        }
+    }
+
+    if let Some(csv_file) = spectrogram_csv {
+        write_spectrogram(&samples, sample_rate, csv_file);
+        println!("Spectrogram written to {}", csv_file);
+    }
+}
+
+/// Averages the channels of `audio` into one.
+pub fn downmix(audio: &AudioData) -> Vec<f32> {
+    audio
+        .samples
+        .chunks(audio.num_channels)
+        .map(|frame| frame.iter().sum::<f32>() / audio.num_channels as f32)
+        .collect()
+}
+
+// Converts an STFT bin's power to dBFS: the Hann window sums to N / 2, and a sine's energy is
+// split between the positive and negative frequencies, so a full-scale sine has magnitude N / 4
+fn bin_level_db(power: f32) -> f32 {
+    let scale = 4.0 / SPECTRUM_FRAME_SIZE as f32;
+    (10.0 * (power * scale * scale).log10()).max(SPECTRUM_FLOOR_DB)
+}
+
+/// Returns the level in dBFS of every bin, from DC to Nyquist, averaged over the whole input.
+pub fn average_spectrum(samples: &[f32]) -> Vec<f32> {
+    let stft = Stft::new(SPECTRUM_FRAME_SIZE, SPECTRUM_HOP_SIZE);
+    let frames = stft.analyze(samples);
+
+    let mut power = vec![0.0_f32; stft.num_bins()];
+    for frame in frames.iter() {
+        for (sum, value) in power.iter_mut().zip(frame) {
+            *sum += value.norm_sqr();
+        }
+    }
+    let num_frames = frames.len().max(1) as f32;
+    power.into_iter().map(|sum| bin_level_db(sum / num_frames)).collect()
+}
+
+/// Returns the bins of the `count` strongest local maxima in `levels`, strongest first.
+pub fn spectrum_peaks(levels: &[f32], count: usize) -> Vec<usize> {
+    let mut peaks: Vec<usize> = (1..levels.len().saturating_sub(1))
+        .filter(|&bin| levels[bin] > levels[bin - 1] && levels[bin] >= levels[bin + 1])
+        .filter(|&bin| levels[bin] > SPECTRUM_FLOOR_DB)
+        .collect();
+    peaks.sort_by(|&a, &b| levels[b].total_cmp(&levels[a]));
+    peaks.truncate(count);
+    peaks
+}
+
+/// Returns the power-weighted mean bin of a spectrum in dB, as a fractional bin index.
+pub fn spectral_centroid(levels: &[f32]) -> f32 {
+    let (weighted, total) = levels.iter().enumerate().fold((0.0_f64, 0.0_f64), |(weighted, total), (bin, &level)| {
+        let power = 10.0_f64.powf(level as f64 / 10.0);
+        (weighted + bin as f64 * power, total + power)
+    });
+    if total > 0.0 {
+        (weighted / total) as f32
+    } else {
+        0.0
+    }
+}
+
+// Writes one row per STFT frame: the time of the frame centre, then the level of every bin
+fn write_spectrogram(samples: &[f32], sample_rate: u32, csv_file: &str) {
+    let stft = Stft::new(SPECTRUM_FRAME_SIZE, SPECTRUM_HOP_SIZE);
+    let padding = SPECTRUM_FRAME_SIZE - SPECTRUM_HOP_SIZE;
+
+    let mut csv = String::from("time_s");
+    for bin in 0..stft.num_bins() {
+        let _ = write!(csv, ",{:.2}", stft.bin_frequency(bin, sample_rate as f32));
+    }
+    csv.push('\n');
+
+    for (index, frame) in stft.analyze(samples).iter().enumerate() {
+        let centre = (index * SPECTRUM_HOP_SIZE + SPECTRUM_FRAME_SIZE / 2) as f32 - padding as f32;
+        let _ = write!(csv, "{:.4}", centre / sample_rate as f32);
+        for value in frame {
+            let _ = write!(csv, ",{:.2}", bin_level_db(value.norm_sqr()));
+        }
+        csv.push('\n');
+    }
+
+    fs::write(csv_file, csv).expect("Failed to write spectrogram CSV file");
+}
+
+/// Returns the half-wave rectified log energy difference between consecutive frames.
+fn onset_envelope(samples: &[f32]) -> Vec<f32> {
+    let log_energies: Vec<f32> = samples
+        .windows(FRAME_SIZE)
+        .step_by(HOP_SIZE)
+        .map(|frame| {
+            let energy = frame.iter().map(|s| s * s).sum::<f32>() / FRAME_SIZE as f32;
+            (energy + 1e-10).ln()
+        })
+        .collect();
+
+    log_energies
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).max(0.0))
+        .collect()
+}
+
+/// Estimates the tempo of `samples` in beats per minute, or `None` if the input is too short.
+pub fn estimate_bpm(samples: &[f32], sample_rate: u32) -> Option<f32> {
+    let mut envelope = onset_envelope(samples);
+    let frame_rate = sample_rate as f32 / HOP_SIZE as f32;
+
+    let min_lag = (60.0 * frame_rate / MAX_BPM).floor() as usize;
+    let max_lag = (60.0 * frame_rate / MIN_BPM).ceil() as usize;
+    if min_lag < 1 || envelope.len() < 2 * max_lag {
+        return None;
+    }
+
+    let mean = envelope.iter().sum::<f32>() / envelope.len() as f32;
+    for value in envelope.iter_mut() {
+        *value -= mean;
+    }
+
+    let autocorrelation: Vec<f32> = (0..=max_lag + 1)
+        .map(|lag| {
+            envelope[lag..]
+                .iter()
+                .zip(envelope.iter())
+                .map(|(a, b)| a * b)
+                .sum::<f32>()
+                / (envelope.len() - lag) as f32
+        })
+        .collect();
+
+    // Weight each candidate lag by how plausible its tempo is
+    let weighted = |lag: usize| {
+        let bpm = 60.0 * frame_rate / lag as f32;
+        let octaves = (bpm / PREFERRED_BPM).log2() / TEMPO_PRIOR_WIDTH;
+        autocorrelation[lag] * (-0.5 * octaves * octaves).exp()
+    };
+
+    let best_lag = (min_lag..=max_lag).max_by(|&a, &b| weighted(a).total_cmp(&weighted(b)))?;
+    if autocorrelation[best_lag] <= 0.0 {
+        return None;
+    }
+
+    // Refine the peak position with parabolic interpolation
+    let (prev, peak, next) = (
+        autocorrelation[best_lag - 1],
+        autocorrelation[best_lag],
+        autocorrelation[best_lag + 1],
+    );
+    let denominator = prev - 2.0 * peak + next;
+    let offset = if denominator.abs() > f32::EPSILON {
+        (0.5 * (prev - next) / denominator).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+
+    Some(60.0 * frame_rate / (best_lag as f32 + offset))
+}
+
+/// Returns the name of the equal-tempered note closest to `frequency`, e.g. "A4".
+pub fn note_name(frequency: f32) -> String {
+    let midi_note = (69.0 + 12.0 * (frequency / 440.0).log2()).round() as i32;
+    let octave = midi_note.div_euclid(12) - 1;
+    format!("{}{}", NOTE_NAMES[midi_note.rem_euclid(12) as usize], octave)
+}
+
+/// Estimates the fundamental frequency of a single frame with YIN, or `None` if it is unvoiced.
+fn yin_pitch(frame: &[f32], sample_rate: u32) -> Option<f32> {
+    let min_period = (sample_rate as f32 / MAX_PITCH_HZ) as usize;
+    let max_period = (sample_rate as f32 / MIN_PITCH_HZ) as usize;
+
+    let rms = (frame[..PITCH_WINDOW].iter().map(|s| s * s).sum::<f32>() / PITCH_WINDOW as f32).sqrt();
+    if rms < SILENCE_RMS {
+        return None;
+    }
+
+    // Cumulative mean normalized difference function
+    let mut cmndf = vec![1.0; max_period + 2];
+    let mut running_sum = 0.0;
+    for tau in 1..cmndf.len() {
+        let difference: f32 = (0..PITCH_WINDOW)
+            .map(|j| {
+                let delta = frame[j] - frame[j + tau];
+                delta * delta
+            })
+            .sum();
+        running_sum += difference;
+        cmndf[tau] = if running_sum > 0.0 {
+            difference * tau as f32 / running_sum
+        } else {
+            1.0
+        };
+    }
+
+    let mut tau = min_period.max(2);
+    while tau <= max_period {
+        if cmndf[tau] < YIN_THRESHOLD {
+            // Walk down to the bottom of this dip
+            while tau < max_period && cmndf[tau + 1] < cmndf[tau] {
+                tau += 1;
+            }
+            let (prev, current, next) = (cmndf[tau - 1], cmndf[tau], cmndf[tau + 1]);
+            let denominator = prev - 2.0 * current + next;
+            let offset = if denominator.abs() > f32::EPSILON {
+                (0.5 * (prev - next) / denominator).clamp(-0.5, 0.5)
+            } else {
+                0.0
+            };
+            return Some(sample_rate as f32 / (tau as f32 + offset));
+        }
+        tau += 1;
+    }
+
+    None
+}
+
+/// Tracks the fundamental frequency frame by frame; unvoiced or silent frames are `None`.
+pub fn pitch_track(samples: &[f32], sample_rate: u32) -> Vec<Option<f32>> {
+    let frame_size = PITCH_WINDOW + (sample_rate as f32 / MIN_PITCH_HZ) as usize + 2;
+
+    samples
+        .windows(frame_size)
+        .step_by(PITCH_HOP)
+        .map(|frame| yin_pitch(frame, sample_rate))
+        .collect()
+}
+
+/// Returns the energy of each of the 12 pitch classes (0 = C) summed over the whole input.
+pub fn chroma(samples: &[f32], sample_rate: u32) -> [f32; 12] {
+    let mut chroma = [0.0; 12];
+    // Hann window to limit leakage between neighbouring semitones
+    let window: Vec<f32> = (0..CHROMA_FRAME_SIZE)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (CHROMA_FRAME_SIZE - 1) as f32).cos())
+        .collect();
+
+    for frame in samples.chunks_exact(CHROMA_FRAME_SIZE) {
+        let windowed: Vec<f32> = frame.iter().zip(&window).map(|(s, w)| s * w).collect();
+        for midi_note in CHROMA_LOWEST_MIDI_NOTE..CHROMA_LOWEST_MIDI_NOTE + 12 * CHROMA_OCTAVES {
+            let frequency = 440.0 * 2.0_f32.powf((midi_note as f32 - 69.0) / 12.0);
+            if frequency >= sample_rate as f32 / 2.0 {
+                break;
+            }
+            chroma[(midi_note % 12) as usize] += goertzel_power(&windowed, frequency, sample_rate as f32).sqrt();
+        }
+    }
+
+    chroma
+}
+
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+    let mean_a = a.iter().sum::<f32>() / a.len() as f32;
+    let mean_b = b.iter().sum::<f32>() / b.len() as f32;
+    let covariance: f32 = a.iter().zip(b).map(|(x, y)| (x - mean_a) * (y - mean_b)).sum();
+    let variance_a: f32 = a.iter().map(|x| (x - mean_a) * (x - mean_a)).sum();
+    let variance_b: f32 = b.iter().map(|y| (y - mean_b) * (y - mean_b)).sum();
+    covariance / (variance_a * variance_b).sqrt().max(f32::EPSILON)
+}
+
+/// Estimates the musical key of `samples`, or `None` if there is no tonal content.
+pub fn estimate_key(samples: &[f32], sample_rate: u32) -> Option<Key> {
+    let chroma = chroma(samples, sample_rate);
+    if chroma.iter().all(|&energy| energy <= f32::EPSILON) {
+        return None;
+    }
+
+    let mut best: Option<(Key, f32)> = None;
+    for tonic in 0..12 {
+        // Rotate the chroma so that the candidate tonic lines up with the profile root
+        let rotated: Vec<f32> = (0..12).map(|i| chroma[(tonic + i) % 12]).collect();
+        for (minor, profile) in [(false, &MAJOR_PROFILE), (true, &MINOR_PROFILE)] {
+            let score = correlation(&rotated, profile);
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((Key { tonic, minor }, score));
+            }
+        }
+    }
+
+    best.map(|(key, _)| key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const RATE: u32 = 48000;
+
+    fn sine(frequency: f32, amplitude: f32, len: usize) -> Vec<f32> {
+        (0..len).map(|n| amplitude * (2.0 * PI * frequency * n as f32 / RATE as f32).sin()).collect()
+    }
+
+    // Short decaying clicks at the given tempo
+    fn clicks(bpm: f32, secs: f32) -> Vec<f32> {
+        let period = (60.0 / bpm * RATE as f32) as usize;
+        (0..(secs * RATE as f32) as usize)
+            .map(|n| {
+                let phase = n % period;
+                if phase < 480 { (1.0 - phase as f32 / 480.0) * if n % 2 == 0 { 1.0 } else { -1.0 } } else { 0.0 }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn downmix_averages_channels() {
+        let stereo = AudioData::new(vec![1.0, 0.0, 0.5, -0.5], RATE, 2);
+        assert_eq!(downmix(&stereo), vec![0.5, 0.0]);
+        let mono = AudioData::new(vec![0.25, -1.0], RATE, 1);
+        assert_eq!(downmix(&mono), mono.samples);
+    }
+
+    #[test]
+    fn note_names() {
+        assert_eq!(note_name(440.0), "A4");
+        assert_eq!(note_name(261.63), "C4");
+        assert_eq!(note_name(27.5), "A0");
+        assert_eq!(note_name(450.0), "A4");
+    }
+
+    #[test]
+    fn tempo_of_a_click_track() {
+        for bpm in [90.0, 120.0, 140.0] {
+            let estimate = estimate_bpm(&clicks(bpm, 20.0), RATE).unwrap();
+            assert!((estimate - bpm).abs() < 2.0, "{} BPM read as {}", bpm, estimate);
+        }
+    }
+
+    #[test]
+    fn tempo_needs_enough_input() {
+        assert_eq!(estimate_bpm(&clicks(120.0, 1.0), RATE), None);
+        assert_eq!(estimate_bpm(&[0.5], RATE), None);
+        assert_eq!(estimate_bpm(&vec![0.0; 20 * RATE as usize], RATE), None);
+    }
+
+    #[test]
+    fn pitch_of_a_sine() {
+        let track = pitch_track(&sine(220.0, 0.5, RATE as usize), RATE);
+        assert!(!track.is_empty());
+        for pitch in track {
+            assert!((pitch.unwrap() - 220.0).abs() < 1.0);
+        }
+    }
+
+    #[test]
+    fn silence_is_unvoiced() {
+        let track = pitch_track(&vec![0.0; RATE as usize], RATE);
+        assert!(!track.is_empty() && track.iter().all(Option::is_none));
+        assert!(pitch_track(&[0.5], RATE).is_empty());
+    }
+
+    #[test]
+    fn key_of_a_triad() {
+        // C E G
+        let triad: Vec<f32> = [261.63, 329.63, 392.0]
+            .iter()
+            .map(|&frequency| sine(frequency, 0.3, 2 * RATE as usize))
+            .reduce(|sum, tone| sum.iter().zip(&tone).map(|(a, b)| a + b).collect())
+            .unwrap();
+        assert_eq!(estimate_key(&triad, RATE), Some(Key { tonic: 0, minor: false }));
+        assert_eq!(Key { tonic: 9, minor: true }.to_string(), "A minor");
+    }
+
+    #[test]
+    fn silence_has_no_key() {
+        assert_eq!(estimate_key(&vec![0.0; 2 * RATE as usize], RATE), None);
+        assert_eq!(estimate_key(&[0.5], RATE), None);
+    }
+
+    #[test]
+    fn full_scale_sine_reads_0_dbfs() {
+        // A frequency on a bin centre, so there is no scalloping loss
+        let bin_width = RATE as f32 / SPECTRUM_FRAME_SIZE as f32;
+        let levels = average_spectrum(&sine(100.0 * bin_width, 1.0, RATE as usize));
+        let peaks = spectrum_peaks(&levels, SPECTRUM_PEAKS);
+        assert_eq!(peaks[0], 100);
+        // The padded frames at either end pull the average down a little
+        assert!(levels[100].abs() < 0.5, "{}", levels[100]);
+        assert!((spectral_centroid(&levels) - 100.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn silent_spectrum_sits_on_the_floor() {
+        let levels = average_spectrum(&vec![0.0; 8192]);
+        assert!(levels.iter().all(|&level| level == SPECTRUM_FLOOR_DB));
+        assert!(spectrum_peaks(&levels, SPECTRUM_PEAKS).is_empty());
+    }
+
+    #[test]
+    fn peaks_are_local_maxima_strongest_first() {
+        let levels = [-60.0, -10.0, -50.0, -40.0, -20.0, -30.0, -5.0];
+        // The last bin has no upper neighbour and is not a peak
+        assert_eq!(spectrum_peaks(&levels, 5), vec![1, 4]);
+        assert_eq!(spectrum_peaks(&levels, 1), vec![1]);
+        assert_eq!(spectral_centroid(&[SPECTRUM_FLOOR_DB, 0.0, SPECTRUM_FLOOR_DB]), 1.0);
+    }
+}
+
@@ -0,0 +1,327 @@
+/*
+Reading and writing audio files.
+Effects work on `AudioData`: interleaved f32 samples normalized to [-1, 1], whatever the bit depth
+or sample format of the file they came from. Integer files are scaled by their full-scale value,
+float files are taken as they are. Output is written as 16-bit PCM; samples beyond full scale
+saturate, so callers that care about clipping should check before writing.
+`read_audio_file` also decodes MP3, FLAC and Ogg Vorbis through symphonia. The format is
+detected from the file header, not the extension: RIFF files go to the WAV reader above and
+everything else to symphonia's probe. Packets that fail to decode are skipped, as players do.
+The `_bytes` and `_to` variants work on in-memory data and any writer, for piping through
+standard input and output. Raw input is headerless 16-bit little-endian PCM.
+ */
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, Write};
+use std::path::Path;
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Full-scale value of 16-bit samples.
+pub const I16_FULL_SCALE: f32 = 32_768.0;
+
+/// How far `sample` lies beyond the range 16-bit output can hold, as a ratio to the limit it
+/// crossed: 32767/32768 for positive samples, -1.0 for negative ones. `None` when it fits.
+pub fn i16_overshoot(sample: f32) -> Option<f32> {
+    let limit = if sample >= 0.0 { i16::MAX as f32 / I16_FULL_SCALE } else { 1.0 };
+    let ratio = sample.abs() / limit;
+    (ratio > 1.0).then_some(ratio)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioData {
+    /// Interleaved samples in [-1, 1].
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub num_channels: usize,
+}
+
+impl AudioData {
+    pub fn new(samples: Vec<f32>, sample_rate: u32, num_channels: usize) -> Self {
+        AudioData {
+            samples,
+            sample_rate,
+            num_channels: num_channels.max(1),
+        }
+    }
+
+    pub fn num_frames(&self) -> usize {
+        self.samples.len() / self.num_channels
+    }
+
+    pub fn duration_secs(&self) -> f32 {
+        self.num_frames() as f32 / self.sample_rate as f32
+    }
+}
+
+/// Reads a WAV file of any integer bit depth or 32-bit float.
+pub fn read_wav<P: AsRef<Path>>(path: P) -> Result<AudioData, hound::Error> {
+    read_wav_from(BufReader::new(File::open(path)?))
+}
+
+/// Reads WAV data from any reader, such as standard input.
+pub fn read_wav_from<R: Read>(reader: R) -> Result<AudioData, hound::Error> {
+    let mut reader = hound::WavReader::new(reader)?;
+    let spec = reader.spec();
+    if spec.sample_rate == 0 {
+        return Err(hound::Error::FormatError("sample rate of zero"));
+    }
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+
+    Ok(AudioData::new(samples, spec.sample_rate, spec.channels as usize))
+}
+
+/// Errors from `read_audio_file`.
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    Wav(hound::Error),
+    Decode(DecodeError),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadError::Io(e) => write!(f, "{}", e),
+            ReadError::Wav(e) => write!(f, "{}", e),
+            ReadError::Decode(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+impl From<io::Error> for ReadError {
+    fn from(e: io::Error) -> Self {
+        ReadError::Io(e)
+    }
+}
+
+impl From<hound::Error> for ReadError {
+    fn from(e: hound::Error) -> Self {
+        ReadError::Wav(e)
+    }
+}
+
+impl From<DecodeError> for ReadError {
+    fn from(e: DecodeError) -> Self {
+        ReadError::Decode(e)
+    }
+}
+
+/// Reads a WAV, MP3, FLAC or Ogg Vorbis file, detecting the format from its header.
+pub fn read_audio_file<P: AsRef<Path>>(path: P) -> Result<AudioData, ReadError> {
+    read_audio_bytes(fs::read(path)?)
+}
+
+/// Decodes a whole WAV, MP3, FLAC or Ogg Vorbis file held in memory.
+pub fn read_audio_bytes(bytes: Vec<u8>) -> Result<AudioData, ReadError> {
+    if bytes.starts_with(b"RIFF") {
+        Ok(read_wav_from(Cursor::new(bytes))?)
+    } else {
+        decode(Box::new(Cursor::new(bytes)))
+    }
+}
+
+/// Interprets headerless 16-bit little-endian PCM; a trailing partial frame is dropped.
+pub fn read_raw_pcm(bytes: &[u8], sample_rate: u32, num_channels: usize) -> AudioData {
+    let num_channels = num_channels.max(1);
+    let frame_bytes = 2 * num_channels;
+    let samples = bytes[..bytes.len() / frame_bytes * frame_bytes]
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]) as f32 / I16_FULL_SCALE)
+        .collect();
+    AudioData::new(samples, sample_rate, num_channels)
+}
+
+// Decodes the first audio track of a compressed file into interleaved samples
+fn decode(source: Box<dyn MediaSource>) -> Result<AudioData, ReadError> {
+    let stream = MediaSourceStream::new(source, Default::default());
+    let probed = symphonia::default::get_probe().format(
+        &Hint::new(),
+        stream,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or(DecodeError::Unsupported("no audio track"))?;
+    let track_id = track.id;
+    let mut sample_rate = track.codec_params.sample_rate;
+    let mut num_channels = track.codec_params.channels.map(|channels| channels.count()).unwrap_or(1);
+    let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut samples = Vec::new();
+    let mut buffer: Option<SampleBuffer<f32>> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            // The end of the stream shows up as an unexpected end of file
+            Err(DecodeError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(DecodeError::ResetRequired) => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(DecodeError::DecodeError(_)) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let spec = *decoded.spec();
+        sample_rate = Some(spec.rate);
+        num_channels = spec.channels.count();
+
+        let needed = decoded.capacity() * num_channels;
+        if buffer.as_ref().is_none_or(|buffer| buffer.capacity() < needed) {
+            buffer = Some(SampleBuffer::new(decoded.capacity() as u64, spec));
+        }
+        if let Some(buffer) = buffer.as_mut() {
+            buffer.copy_interleaved_ref(decoded);
+            samples.extend_from_slice(buffer.samples());
+        }
+    }
+
+    // A stream that never states its rate cannot be played back or resampled
+    let sample_rate = sample_rate
+        .filter(|&rate| rate > 0)
+        .ok_or(DecodeError::Unsupported("missing sample rate"))?;
+    Ok(AudioData::new(samples, sample_rate, num_channels))
+}
+
+/// Writes `audio` as a 16-bit PCM WAV file.
+pub fn write_wav<P: AsRef<Path>>(path: P, audio: &AudioData) -> Result<(), hound::Error> {
+    write_wav_to(BufWriter::new(File::create(path)?), audio)
+}
+
+/// Writes `audio` as 16-bit PCM WAV data to any seekable writer. The header is completed when
+/// the writer is finalized, so stream it through an in-memory buffer.
+pub fn write_wav_to<W: Write + Seek>(writer: W, audio: &AudioData) -> Result<(), hound::Error> {
+    let spec = hound::WavSpec {
+        channels: audio.num_channels as u16,
+        sample_rate: audio.sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::new(writer, spec)?;
+    for &sample in audio.samples.iter() {
+        // The cast saturates at the i16 range
+        writer.write_sample((sample * I16_FULL_SCALE) as i16)?;
+    }
+    writer.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wav_bytes(audio: &AudioData) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        write_wav_to(&mut cursor, audio).unwrap();
+        cursor.into_inner()
+    }
+
+    // One 16-bit quantization step
+    const STEP: f32 = 1.0 / I16_FULL_SCALE;
+
+    #[test]
+    fn wav_round_trip_keeps_the_interleaved_layout() {
+        // Left and right ramps in opposite directions, so swapped channels would show
+        let samples: Vec<f32> = (0..100).flat_map(|i| [i as f32 / 100.0, -(i as f32) / 100.0]).collect();
+        let audio = AudioData::new(samples, 44100, 2);
+        let read = read_audio_bytes(wav_bytes(&audio)).unwrap();
+        assert_eq!(read.sample_rate, 44100);
+        assert_eq!(read.num_channels, 2);
+        assert_eq!(read.num_frames(), 100);
+        for (actual, expected) in read.samples.iter().zip(&audio.samples) {
+            assert!((actual - expected).abs() <= STEP, "expected {}, got {}", expected, actual);
+        }
+    }
+
+    #[test]
+    fn writing_saturates_beyond_full_scale() {
+        let audio = AudioData::new(vec![1.5, -1.5, 1.0, -1.0], 48000, 1);
+        let read = read_audio_bytes(wav_bytes(&audio)).unwrap();
+        assert_eq!(read.samples, [i16::MAX as f32 * STEP, -1.0, i16::MAX as f32 * STEP, -1.0]);
+    }
+
+    #[test]
+    fn float_wav_is_read_unscaled() {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 48000,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut cursor = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut cursor, spec).unwrap();
+        for sample in [0.25_f32, -2.0, 1.5] {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        assert_eq!(read_audio_bytes(cursor.into_inner()).unwrap().samples, [0.25, -2.0, 1.5]);
+    }
+
+    #[test]
+    fn raw_pcm_drops_a_partial_frame() {
+        let bytes: Vec<u8> = [i16::MIN, 16384, -16384, 0, 1]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        let audio = read_raw_pcm(&bytes, 8000, 2);
+        assert_eq!(audio.sample_rate, 8000);
+        assert_eq!(audio.num_frames(), 2);
+        assert_eq!(audio.samples, [-1.0, 0.5, -0.5, 0.0]);
+        assert_eq!(audio.duration_secs(), 2.0 / 8000.0);
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let path = std::env::temp_dir().join(format!("audiofxrs-test-{}.wav", std::process::id()));
+        let audio = AudioData::new(vec![0.5, -0.25, 0.0], 22050, 1);
+        write_wav(&path, &audio).unwrap();
+        let read = read_audio_file(&path);
+        let _ = fs::remove_file(&path);
+        assert_eq!(read.unwrap(), audio);
+    }
+
+    #[test]
+    fn overshoot_uses_the_asymmetric_i16_range() {
+        assert_eq!(i16_overshoot(-1.0), None);
+        assert_eq!(i16_overshoot(i16::MAX as f32 / I16_FULL_SCALE), None);
+        assert_eq!(i16_overshoot(0.0), None);
+        assert_eq!(i16_overshoot(-2.0), Some(2.0));
+        let overshoot = i16_overshoot(1.0).unwrap();
+        assert!((overshoot - I16_FULL_SCALE / i16::MAX as f32).abs() < 1e-7);
+    }
+
+    #[test]
+    fn zero_sample_rate_is_rejected() {
+        let mut bytes = wav_bytes(&AudioData::new(vec![0.0; 16], 48000, 1));
+        // The fmt chunk starts at byte 12; the rate and byte rate follow the channel count
+        bytes[24..32].fill(0);
+        assert!(read_audio_bytes(bytes).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+/*
+Batch processing: every input file (or every match of a glob pattern) is run through the same
+effect and written to the output directory under its own file name. Patterns are expanded here
+because quoted patterns reach the program unexpanded, and a file that fails is reported and
+skipped instead of stopping the whole batch. Inputs whose outputs would share a path (the same
+file name in different directories) all fail before anything is written.
+With --jobs, files are handed out to that many threads, each with its own copy of the effect; a
+copy that produced NaN or infinite samples is replaced by a fresh one before the next file.
+Results and their warnings are printed in input order once all files are done, so the report
+does not depend on which thread finished first.
+ */
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use audiofxrs::AudioEffect;
+
+use crate::process::{self, CommonOptions, FileReport};
+
+type FileResult = Result<(PathBuf, PathBuf, FileReport), String>;
+
+// Expands the arguments into input files, or an error for a pattern that matches nothing
+fn expand_inputs(patterns: &[String]) -> Vec<Result<PathBuf, String>> {
+    let mut inputs = Vec::new();
+    for pattern in patterns {
+        if !pattern.contains(['*', '?', '[']) {
+            inputs.push(Ok(PathBuf::from(pattern)));
+            continue;
+        }
+        match glob::glob(pattern) {
+            Ok(paths) => {
+                let mut matches: Vec<PathBuf> = paths.filter_map(Result::ok).filter(|path| path.is_file()).collect();
+                if matches.is_empty() {
+                    inputs.push(Err(format!("{}: no files match", pattern)));
+                }
+                matches.sort();
+                inputs.extend(matches.into_iter().map(Ok));
+            }
+            Err(e) => inputs.push(Err(format!("{}: invalid pattern ({})", pattern, e))),
+        }
+    }
+    inputs
+}
+
+fn output_path(input: &Path, out_dir: &Path) -> Result<PathBuf, String> {
+    let file_name = input
+        .file_name()
+        .ok_or_else(|| format!("{}: not a file name", input.display()))?;
+    let output = out_dir.join(file_name);
+    // Never write over the file being read
+    if output.canonicalize().ok().is_some_and(|output| input.canonicalize().ok() == Some(output)) {
+        return Err(format!("{}: the output would overwrite the input", input.display()));
+    }
+    Ok(output)
+}
+
+// Pairs every input with its output path. Inputs from different directories can share a file
+// name; all of them are refused rather than letting one overwrite another (or race on it)
+fn plan_outputs(inputs: Vec<Result<PathBuf, String>>, out_dir: &Path) -> Vec<Result<(PathBuf, PathBuf), String>> {
+    let planned: Vec<_> = inputs
+        .into_iter()
+        .map(|input| input.and_then(|input| Ok((output_path(&input, out_dir)?, input))))
+        .collect();
+
+    let mut users: HashMap<&Path, Vec<&Path>> = HashMap::new();
+    for (output, input) in planned.iter().flatten() {
+        users.entry(output).or_default().push(input);
+    }
+    let clashes: HashMap<PathBuf, String> = users
+        .into_iter()
+        .filter(|(_, inputs)| inputs.len() > 1)
+        .map(|(output, inputs)| {
+            let names: Vec<String> = inputs.iter().map(|input| input.display().to_string()).collect();
+            (output.to_path_buf(), names.join(", "))
+        })
+        .collect();
+
+    planned
+        .into_iter()
+        .map(|plan| {
+            let (output, input) = plan?;
+            match clashes.get(&output) {
+                Some(names) => Err(format!(
+                    "{}: {} would be written by each of {}",
+                    input.display(),
+                    output.display(),
+                    names
+                )),
+                None => Ok((input, output)),
+            }
+        })
+        .collect()
+}
+
+fn process_input<E: AudioEffect>(
+    effect: &mut E,
+    plan: &Result<(PathBuf, PathBuf), String>,
+    options: &CommonOptions,
+) -> FileResult {
+    let (input, output) = plan.clone()?;
+    let report = process::process_file(effect, &input.to_string_lossy(), &output.to_string_lossy(), options)?;
+    Ok((input, output, report))
+}
+
+/// Processes every input in `options.files` into `out_dir` and exits with an error if any failed.
+pub fn run<E: AudioEffect + Clone + Send>(effect: &E, out_dir: &Path, options: &CommonOptions) {
+    if let Err(e) = fs::create_dir_all(out_dir) {
+        eprintln!("Failed to create output directory {}: {}", out_dir.display(), e);
+        std::process::exit(1);
+    }
+
+    let inputs = plan_outputs(expand_inputs(&options.files), out_dir);
+    let total = inputs.len();
+    let jobs = options.jobs.clamp(1, total.max(1));
+
+    // Each worker takes the next unprocessed input until none are left
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, FileResult)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                // A pristine copy per worker, so only `Send` is needed to reset after a blow-up
+                let pristine = effect.clone();
+                let mut worker_effect = pristine.clone();
+                let (next, inputs) = (&next, &inputs);
+                scope.spawn(move || {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(input) = inputs.get(index) else {
+                            break;
+                        };
+                        let result = process_input(&mut worker_effect, input, options);
+                        // An effect that blew up may keep NaNs in its state; start the next file fresh
+                        if matches!(&result, Ok((_, _, report)) if report.replaced_output_samples > 0) {
+                            worker_effect = pristine.clone();
+                        }
+                        done.push((index, result));
+                    }
+                    done
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("Batch worker panicked"))
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+
+    let mut failed = 0;
+    for (_, result) in results {
+        match result {
+            Ok((input, output, report)) => {
+                println!("ok      {} -> {}", input.display(), output.display());
+                report.print(effect.name(), false);
+            }
+            Err(error) => {
+                failed += 1;
+                eprintln!("FAILED  {}", error);
+            }
+        }
+    }
+
+    println!("Processed {} of {} file(s) into {}", total - failed, total, out_dir.display());
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_with_the_same_file_name_are_refused() {
+        let inputs = ["a/x.wav", "b/x.wav", "c/y.wav"].map(|path| Ok(PathBuf::from(path))).to_vec();
+        let plans = plan_outputs(inputs, Path::new("out"));
+        assert!(plans[0].as_ref().unwrap_err().contains("b/x.wav"));
+        assert!(plans[1].as_ref().unwrap_err().contains("a/x.wav"));
+        assert_eq!(plans[2], Ok((PathBuf::from("c/y.wav"), PathBuf::from("out/y.wav"))));
+    }
+
+    #[test]
+    fn earlier_errors_are_kept() {
+        let inputs = vec![Err("*.flac: no files match".to_string()), Ok(PathBuf::from("x.wav"))];
+        let plans = plan_outputs(inputs, Path::new("out"));
+        assert_eq!(plans[0], Err("*.flac: no files match".to_string()));
+        assert_eq!(plans[1], Ok((PathBuf::from("x.wav"), PathBuf::from("out/x.wav"))));
+    }
+}
@@ -0,0 +1,88 @@
+/*
+Null testing: subtracts one file from another and reports what is left.
+The files must have the same sample rate and channel count. They are compared sample by sample
+from the start, with the shorter one extended by silence, so a length difference shows up in
+the residual as well as in the report. Identical files leave a residual of exact zeros; the null
+depth is how far the residual sits below the first file's level.
+ */
+use audiofxrs::audio_io::{self, AudioData};
+use audiofxrs::dsp::envelope::linear_to_db;
+
+use crate::process;
+
+fn rms(samples: &[f32]) -> f32 {
+    let sum: f64 = samples.iter().map(|&s| (s as f64).powi(2)).sum();
+    (sum / samples.len().max(1) as f64).sqrt() as f32
+}
+
+pub fn run(args: &[String]) {
+    let mut residual_file = None;
+    let mut positional = Vec::new();
+
+    let mut options = args[2..].iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--residual" => residual_file = Some(options.next().expect("Missing file name after --residual")),
+            _ if arg.starts_with("--") => {
+                eprintln!("Unknown diff option: {}", arg);
+                std::process::exit(1);
+            }
+            _ => positional.push(arg),
+        }
+    }
+    if positional.len() != 2 {
+        eprintln!("Usage: {} diff <a.wav> <b.wav> [--residual <output.wav>]", args[0]);
+        std::process::exit(1);
+    }
+
+    let a = process::read_or_exit(positional[0], None);
+    let b = process::read_or_exit(positional[1], None);
+    if a.sample_rate != b.sample_rate || a.num_channels != b.num_channels {
+        eprintln!(
+            "Cannot compare {} Hz / {} channel(s) with {} Hz / {} channel(s)",
+            a.sample_rate, a.num_channels, b.sample_rate, b.num_channels
+        );
+        std::process::exit(1);
+    }
+    if a.num_frames() != b.num_frames() {
+        println!("Length: {} vs {} frames", a.num_frames(), b.num_frames());
+    }
+
+    let length = a.samples.len().max(b.samples.len());
+    let residual: Vec<f32> = (0..length)
+        .map(|i| a.samples.get(i).copied().unwrap_or(0.0) - b.samples.get(i).copied().unwrap_or(0.0))
+        .collect();
+
+    let (max_index, max_difference) = residual
+        .iter()
+        .enumerate()
+        .fold((0, 0.0_f32), |(index, max), (i, &d)| if d.abs() > max { (i, d.abs()) } else { (index, max) });
+    let differing = residual.iter().filter(|&&d| d != 0.0).count();
+
+    if differing == 0 {
+        println!("Identical: the residual is digital silence");
+    } else {
+        let frame = max_index / a.num_channels;
+        println!(
+            "Max difference: {:.2} dBFS ({:.6}) at {:.4} s, channel {}",
+            linear_to_db(max_difference),
+            max_difference,
+            frame as f32 / a.sample_rate as f32,
+            max_index % a.num_channels + 1
+        );
+        let residual_rms = rms(&residual);
+        println!(
+            "Residual RMS: {:.2} dBFS, null depth {:.2} dB, {} of {} sample(s) differ",
+            linear_to_db(residual_rms),
+            linear_to_db(rms(&a.samples)) - linear_to_db(residual_rms),
+            differing,
+            length
+        );
+    }
+
+    if let Some(residual_file) = residual_file {
+        let residual = AudioData::new(residual, a.sample_rate, a.num_channels);
+        audio_io::write_wav(residual_file, &residual).expect("Failed to write residual file");
+        println!("Residual written to {}", residual_file);
+    }
+}
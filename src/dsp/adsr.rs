@@ -0,0 +1,238 @@
+/*
+An ADSR envelope generator for effects that react to triggers (gated reverb, stutter, duckers,
+tape stop).
+Each stage is a linear segment whose length is counted in samples, so transitions happen on an
+exact sample: attack rises from the current level to 1, decay falls to the sustain level, and
+release falls from wherever the envelope is to 0. Stages with zero length are skipped within
+the same sample. In one-shot mode the envelope goes straight from decay into release without
+waiting for `release()`, which with no decay and full sustain gives a plain AR envelope.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+#[derive(Debug, Clone)]
+pub struct Adsr {
+    sample_rate: f32,
+    attack_samples: usize,
+    decay_samples: usize,
+    sustain: f32,
+    release_samples: usize,
+    one_shot: bool,
+    stage: Stage,
+    position: usize,
+    start_level: f32,
+    level: f32,
+}
+
+fn ms_to_samples(time_ms: f32, sample_rate: f32) -> usize {
+    (time_ms.max(0.0) * 0.001 * sample_rate).round() as usize
+}
+
+impl Adsr {
+    pub fn new(sample_rate: f32, attack_ms: f32, decay_ms: f32, sustain: f32, release_ms: f32) -> Self {
+        Adsr {
+            sample_rate,
+            attack_samples: ms_to_samples(attack_ms, sample_rate),
+            decay_samples: ms_to_samples(decay_ms, sample_rate),
+            sustain: sustain.clamp(0.0, 1.0),
+            release_samples: ms_to_samples(release_ms, sample_rate),
+            one_shot: false,
+            stage: Stage::Idle,
+            position: 0,
+            start_level: 0.0,
+            level: 0.0,
+        }
+    }
+
+    /// A one-shot attack/release envelope that releases as soon as the attack has finished.
+    pub fn attack_release(sample_rate: f32, attack_ms: f32, release_ms: f32) -> Self {
+        let mut envelope = Adsr::new(sample_rate, attack_ms, 0.0, 1.0, release_ms);
+        envelope.set_one_shot(true);
+        envelope
+    }
+
+    pub fn set_attack_ms(&mut self, attack_ms: f32) {
+        self.attack_samples = ms_to_samples(attack_ms, self.sample_rate);
+    }
+
+    pub fn set_decay_ms(&mut self, decay_ms: f32) {
+        self.decay_samples = ms_to_samples(decay_ms, self.sample_rate);
+    }
+
+    pub fn set_sustain(&mut self, sustain: f32) {
+        self.sustain = sustain.clamp(0.0, 1.0);
+    }
+
+    pub fn set_release_ms(&mut self, release_ms: f32) {
+        self.release_samples = ms_to_samples(release_ms, self.sample_rate);
+    }
+
+    /// When enabled, the envelope releases right after the decay instead of sustaining.
+    pub fn set_one_shot(&mut self, one_shot: bool) {
+        self.one_shot = one_shot;
+    }
+
+    /// Starts (or restarts) the attack from the current level, so retriggers do not click.
+    pub fn trigger(&mut self) {
+        self.enter(Stage::Attack);
+    }
+
+    /// Starts the release from the current level.
+    pub fn release(&mut self) {
+        if self.stage != Stage::Idle {
+            self.enter(Stage::Release);
+        }
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.stage != Stage::Idle
+    }
+
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    pub fn reset(&mut self) {
+        self.stage = Stage::Idle;
+        self.level = 0.0;
+    }
+
+    /// Returns the envelope value in [0, 1] for the current sample and advances by one.
+    pub fn next_value(&mut self) -> f32 {
+        // Zero-length stages hand over immediately, so loop until a stage produces a value
+        loop {
+            let (length, target) = match self.stage {
+                Stage::Idle => return 0.0,
+                Stage::Sustain => {
+                    self.level = self.sustain;
+                    return self.level;
+                }
+                Stage::Attack => (self.attack_samples, 1.0),
+                Stage::Decay => (self.decay_samples, self.sustain),
+                Stage::Release => (self.release_samples, 0.0),
+            };
+
+            if self.position < length {
+                self.position += 1;
+                let progress = self.position as f32 / length as f32;
+                self.level = self.start_level + (target - self.start_level) * progress;
+                if self.position == length {
+                    self.advance();
+                }
+                return self.level;
+            }
+
+            self.level = target;
+            self.advance();
+        }
+    }
+
+    fn advance(&mut self) {
+        let next = match self.stage {
+            Stage::Attack => Stage::Decay,
+            Stage::Decay if self.one_shot => Stage::Release,
+            Stage::Decay => Stage::Sustain,
+            Stage::Release | Stage::Idle => Stage::Idle,
+            Stage::Sustain => Stage::Sustain,
+        };
+        self.enter(next);
+    }
+
+    fn enter(&mut self, stage: Stage) {
+        self.stage = stage;
+        self.position = 0;
+        self.start_level = self.level;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::test_util::assert_close;
+
+    // At 1 kHz one millisecond is one sample
+    const SAMPLE_RATE: f32 = 1000.0;
+    const TOLERANCE: f32 = 1e-6;
+
+    #[test]
+    fn stages_are_linear_segments_of_exact_length() {
+        let mut envelope = Adsr::new(SAMPLE_RATE, 10.0, 10.0, 0.5, 20.0);
+        assert_eq!(envelope.next_value(), 0.0);
+        envelope.trigger();
+        for k in 1..=10 {
+            assert_close(envelope.next_value(), k as f32 / 10.0, TOLERANCE);
+        }
+        assert_eq!(envelope.stage(), Stage::Decay);
+        for k in 1..=10 {
+            assert_close(envelope.next_value(), 1.0 - 0.5 * k as f32 / 10.0, TOLERANCE);
+        }
+        assert_eq!(envelope.stage(), Stage::Sustain);
+        for _ in 0..100 {
+            assert_close(envelope.next_value(), 0.5, TOLERANCE);
+        }
+
+        envelope.release();
+        for k in 1..=20 {
+            assert_close(envelope.next_value(), 0.5 - 0.5 * k as f32 / 20.0, TOLERANCE);
+        }
+        assert_eq!(envelope.stage(), Stage::Idle);
+        assert!(!envelope.is_active());
+    }
+
+    #[test]
+    fn attack_release_does_not_wait_for_release() {
+        let mut envelope = Adsr::attack_release(SAMPLE_RATE, 4.0, 8.0);
+        envelope.trigger();
+        let values: Vec<f32> = (0..14).map(|_| envelope.next_value()).collect();
+        assert_close(values[3], 1.0, TOLERANCE);
+        assert_close(values[7], 0.5, TOLERANCE);
+        assert_close(values[11], 0.0, TOLERANCE);
+        assert_eq!(values[13], 0.0);
+        assert_eq!(envelope.stage(), Stage::Idle);
+    }
+
+    #[test]
+    fn retrigger_starts_from_the_current_level() {
+        let mut envelope = Adsr::new(SAMPLE_RATE, 10.0, 0.0, 1.0, 10.0);
+        envelope.trigger();
+        for _ in 0..10 {
+            envelope.next_value();
+        }
+        envelope.release();
+        for _ in 0..5 {
+            envelope.next_value();
+        }
+        assert_close(envelope.level(), 0.5, TOLERANCE);
+        envelope.trigger();
+        assert_close(envelope.next_value(), 0.55, TOLERANCE);
+    }
+
+    #[test]
+    fn zero_length_stages_are_skipped_in_the_same_sample() {
+        let mut envelope = Adsr::new(SAMPLE_RATE, 0.0, 0.0, 0.25, 0.0);
+        envelope.trigger();
+        assert_close(envelope.next_value(), 0.25, TOLERANCE);
+        envelope.release();
+        assert_eq!(envelope.next_value(), 0.0);
+        assert_eq!(envelope.stage(), Stage::Idle);
+    }
+
+    #[test]
+    fn release_while_idle_does_nothing() {
+        let mut envelope = Adsr::new(SAMPLE_RATE, 10.0, 10.0, 0.5, 10.0);
+        envelope.release();
+        assert_eq!(envelope.stage(), Stage::Idle);
+        assert_eq!(envelope.next_value(), 0.0);
+    }
+}
@@ -0,0 +1,142 @@
+/*
+Allpass filters pass every frequency at unity gain and only change the phase.
+The first-order allpass shifts the phase by 180 degrees across the spectrum, reaching 90 degrees
+at its break frequency; a chain of them with a swept break frequency is what produces the moving
+notches of a phaser. The Schroeder allpass wraps a delay line in a feedforward/feedback pair,
+smearing transients in time without colouring them, which is the classic diffusion stage of
+artificial reverbs. The allpass delay uses a first-order allpass as a fractional delay element:
+unlike interpolated reads it keeps the magnitude response flat at every frequency, which is what
+tuned resonators need to stay in pitch without losing brightness.
+ */
+use super::DelayLine;
+use std::f32::consts::PI;
+
+/// y[n] = a * x[n] + x[n - 1] - a * y[n - 1]
+#[derive(Debug, Clone, Default)]
+pub struct FirstOrderAllpass {
+    coefficient: f32,
+    x1: f32,
+    y1: f32,
+}
+
+impl FirstOrderAllpass {
+    pub fn new(coefficient: f32) -> Self {
+        FirstOrderAllpass {
+            coefficient,
+            x1: 0.0,
+            y1: 0.0,
+        }
+    }
+
+    /// Creates an allpass whose phase shift is 90 degrees at `frequency`.
+    pub fn with_frequency(frequency: f32, sample_rate: f32) -> Self {
+        let mut allpass = FirstOrderAllpass::default();
+        allpass.set_frequency(frequency, sample_rate);
+        allpass
+    }
+
+    /// Sets the coefficient directly; it must stay inside (-1, 1) for the filter to be stable.
+    pub fn set_coefficient(&mut self, coefficient: f32) {
+        self.coefficient = coefficient;
+    }
+
+    /// Moves the 90 degree point to `frequency`. Cheap enough to call every sample.
+    pub fn set_frequency(&mut self, frequency: f32, sample_rate: f32) {
+        let t = (PI * frequency.clamp(1.0, 0.49 * sample_rate) / sample_rate).tan();
+        self.coefficient = (t - 1.0) / (t + 1.0);
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = self.coefficient * input + self.x1 - self.coefficient * self.y1;
+        self.x1 = input;
+        self.y1 = output;
+        output
+    }
+
+    pub fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.y1 = 0.0;
+    }
+}
+
+/// Delay-based allpass: v[n] = x[n] + g * v[n - D], y[n] = -g * v[n] + v[n - D]
+#[derive(Debug, Clone)]
+pub struct SchroederAllpass {
+    delay_line: DelayLine,
+    delay: usize,
+    gain: f32,
+}
+
+impl SchroederAllpass {
+    pub fn new(delay: usize, gain: f32) -> Self {
+        SchroederAllpass {
+            delay_line: DelayLine::new(delay),
+            delay: delay.max(1),
+            gain,
+        }
+    }
+
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+    }
+
+    /// Changes the delay, limited to the length the allpass was created with.
+    pub fn set_delay(&mut self, delay: usize) {
+        self.delay = delay.clamp(1, self.delay_line.max_delay());
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.delay_line.read(self.delay);
+        let v = input + self.gain * delayed;
+        self.delay_line.write(v);
+        delayed - self.gain * v
+    }
+
+    pub fn reset(&mut self) {
+        self.delay_line.clear();
+    }
+}
+
+/// A delay with a fractional part realised by a first-order (Thiran) allpass after the integer
+/// delay. The magnitude response is exactly flat; the delay is accurate at low and mid
+/// frequencies. Best suited to slowly changing delays, since the allpass state has to settle
+/// after each change.
+#[derive(Debug, Clone)]
+pub struct AllpassDelay {
+    delay_line: DelayLine,
+    integer_delay: usize,
+    allpass: FirstOrderAllpass,
+}
+
+impl AllpassDelay {
+    /// `max_delay` is the longest delay in samples the line can be retuned to.
+    pub fn new(max_delay: usize, delay: f32) -> Self {
+        let mut allpass_delay = AllpassDelay {
+            delay_line: DelayLine::new(max_delay + 1),
+            integer_delay: 1,
+            allpass: FirstOrderAllpass::default(),
+        };
+        allpass_delay.set_delay(delay);
+        allpass_delay
+    }
+
+    /// Sets the total delay in samples, at least 1.5.
+    pub fn set_delay(&mut self, delay: f32) {
+        let delay = delay.clamp(1.5, self.delay_line.max_delay() as f32);
+        // Keep the fractional part in [0.5, 1.5), where the allpass delay is most accurate
+        self.integer_delay = (delay - 0.5).floor() as usize;
+        let fraction = delay - self.integer_delay as f32;
+        self.allpass.set_coefficient((1.0 - fraction) / (1.0 + fraction));
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.delay_line.read(self.integer_delay);
+        self.delay_line.write(input);
+        self.allpass.process(delayed)
+    }
+
+    pub fn reset(&mut self) {
+        self.delay_line.clear();
+        self.allpass.reset();
+    }
+}
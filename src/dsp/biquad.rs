@@ -0,0 +1,258 @@
+/*
+Second-order IIR filters with the coefficient formulas from Robert Bristow-Johnson's
+"Audio EQ Cookbook".
+The filter runs in transposed direct form II with double precision state, which stays
+well-behaved when the coefficients are updated while audio is running (filter sweeps, wahs)
+and keeps low cutoff frequencies accurate.
+ */
+use std::f64::consts::PI;
+
+/// The response shapes from the cookbook. Peaking and shelving filters carry their gain in dB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterType {
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+    AllPass,
+    Peaking { gain_db: f32 },
+    LowShelf { gain_db: f32 },
+    HighShelf { gain_db: f32 },
+}
+
+/// Normalized biquad coefficients (a0 = 1).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coefficients {
+    pub b0: f64,
+    pub b1: f64,
+    pub b2: f64,
+    pub a1: f64,
+    pub a2: f64,
+}
+
+impl Coefficients {
+    /// Coefficients that pass the signal through unchanged.
+    pub const IDENTITY: Coefficients = Coefficients {
+        b0: 1.0,
+        b1: 0.0,
+        b2: 0.0,
+        a1: 0.0,
+        a2: 0.0,
+    };
+
+    /// Computes the coefficients for `filter_type` at `frequency` Hz with quality factor `q`.
+    /// The frequency is clamped below Nyquist and the Q to a small positive value, so the
+    /// resulting filter is always stable.
+    pub fn new(filter_type: FilterType, sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let sample_rate = sample_rate as f64;
+        let frequency = (frequency as f64).clamp(1e-3, 0.49 * sample_rate);
+        let q = (q as f64).max(1e-3);
+
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        let (sin_w0, cos_w0) = w0.sin_cos();
+        let alpha = sin_w0 / (2.0 * q);
+
+        let (b0, b1, b2, a0, a1, a2) = match filter_type {
+            FilterType::LowPass => (
+                (1.0 - cos_w0) / 2.0,
+                1.0 - cos_w0,
+                (1.0 - cos_w0) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            FilterType::HighPass => (
+                (1.0 + cos_w0) / 2.0,
+                -(1.0 + cos_w0),
+                (1.0 + cos_w0) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            // Constant 0 dB peak gain
+            FilterType::BandPass => (alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha),
+            FilterType::Notch => (1.0, -2.0 * cos_w0, 1.0, 1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha),
+            FilterType::AllPass => (
+                1.0 - alpha,
+                -2.0 * cos_w0,
+                1.0 + alpha,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            FilterType::Peaking { gain_db } => {
+                let a = 10.0_f64.powf(gain_db as f64 / 40.0);
+                (
+                    1.0 + alpha * a,
+                    -2.0 * cos_w0,
+                    1.0 - alpha * a,
+                    1.0 + alpha / a,
+                    -2.0 * cos_w0,
+                    1.0 - alpha / a,
+                )
+            }
+            FilterType::LowShelf { gain_db } => {
+                let a = 10.0_f64.powf(gain_db as f64 / 40.0);
+                let two_sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) - (a - 1.0) * cos_w0 + two_sqrt_a_alpha),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos_w0),
+                    a * ((a + 1.0) - (a - 1.0) * cos_w0 - two_sqrt_a_alpha),
+                    (a + 1.0) + (a - 1.0) * cos_w0 + two_sqrt_a_alpha,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos_w0),
+                    (a + 1.0) + (a - 1.0) * cos_w0 - two_sqrt_a_alpha,
+                )
+            }
+            FilterType::HighShelf { gain_db } => {
+                let a = 10.0_f64.powf(gain_db as f64 / 40.0);
+                let two_sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) + (a - 1.0) * cos_w0 + two_sqrt_a_alpha),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_w0),
+                    a * ((a + 1.0) + (a - 1.0) * cos_w0 - two_sqrt_a_alpha),
+                    (a + 1.0) - (a - 1.0) * cos_w0 + two_sqrt_a_alpha,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos_w0),
+                    (a + 1.0) - (a - 1.0) * cos_w0 - two_sqrt_a_alpha,
+                )
+            }
+        };
+
+        Coefficients {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+}
+
+impl Default for Coefficients {
+    fn default() -> Self {
+        Coefficients::IDENTITY
+    }
+}
+
+/// A single biquad section with its own state. Use one per channel.
+#[derive(Debug, Clone, Default)]
+pub struct Biquad {
+    coefficients: Coefficients,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    pub fn new(coefficients: Coefficients) -> Self {
+        Biquad {
+            coefficients,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// Shorthand for `Biquad::new(Coefficients::new(...))`.
+    pub fn with_type(filter_type: FilterType, sample_rate: f32, frequency: f32, q: f32) -> Self {
+        Biquad::new(Coefficients::new(filter_type, sample_rate, frequency, q))
+    }
+
+    pub fn coefficients(&self) -> Coefficients {
+        self.coefficients
+    }
+
+    /// Replaces the coefficients while keeping the filter state, so the change does not click.
+    pub fn set_coefficients(&mut self, coefficients: Coefficients) {
+        self.coefficients = coefficients;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let c = &self.coefficients;
+        let x = input as f64;
+        let y = c.b0 * x + self.z1;
+        self.z1 = c.b1 * x - c.a1 * y + self.z2;
+        self.z2 = c.b2 * x - c.a2 * y;
+        y as f32
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::test_util::{assert_close, gain_db, SAMPLE_RATE};
+
+    // Steady-state gain in dB of a filter for a sine at `frequency`
+    fn filter_gain_db(filter_type: FilterType, cutoff: f32, q: f32, frequency: f32) -> f32 {
+        let mut filter = Biquad::with_type(filter_type, SAMPLE_RATE, cutoff, q);
+        gain_db(frequency, |x| filter.process(x))
+    }
+
+    const BUTTERWORTH_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+    #[test]
+    fn low_pass_is_3_db_down_at_the_cutoff_and_rolls_off_at_12_db_per_octave() {
+        assert_close(filter_gain_db(FilterType::LowPass, 1000.0, BUTTERWORTH_Q, 100.0), 0.0, 0.05);
+        assert_close(filter_gain_db(FilterType::LowPass, 1000.0, BUTTERWORTH_Q, 1000.0), -3.01, 0.05);
+        // Three octaves up: -36 dB for the analog prototype, a little more after the bilinear warp
+        let stopband = filter_gain_db(FilterType::LowPass, 1000.0, BUTTERWORTH_Q, 8000.0);
+        assert!((-39.0..-36.0).contains(&stopband), "got {}", stopband);
+    }
+
+    #[test]
+    fn high_pass_mirrors_the_low_pass() {
+        assert_close(filter_gain_db(FilterType::HighPass, 1000.0, BUTTERWORTH_Q, 10_000.0), 0.0, 0.1);
+        assert_close(filter_gain_db(FilterType::HighPass, 1000.0, BUTTERWORTH_Q, 1000.0), -3.01, 0.05);
+        assert_close(filter_gain_db(FilterType::HighPass, 1000.0, BUTTERWORTH_Q, 125.0), -36.1, 1.0);
+    }
+
+    #[test]
+    fn band_pass_and_notch_act_at_the_centre_frequency() {
+        assert_close(filter_gain_db(FilterType::BandPass, 1000.0, 2.0, 1000.0), 0.0, 0.05);
+        assert!(filter_gain_db(FilterType::BandPass, 1000.0, 2.0, 100.0) < -20.0);
+        assert!(filter_gain_db(FilterType::Notch, 1000.0, 2.0, 1000.0) < -40.0);
+        assert_close(filter_gain_db(FilterType::Notch, 1000.0, 2.0, 100.0), 0.0, 0.1);
+    }
+
+    #[test]
+    fn all_pass_keeps_the_level_at_every_frequency() {
+        for frequency in [50.0, 500.0, 1000.0, 5000.0, 15_000.0] {
+            assert_close(filter_gain_db(FilterType::AllPass, 1000.0, 1.0, frequency), 0.0, 0.05);
+        }
+    }
+
+    #[test]
+    fn peaking_and_shelves_apply_their_gain() {
+        let boost = FilterType::Peaking { gain_db: 6.0 };
+        assert_close(filter_gain_db(boost, 1000.0, 1.0, 1000.0), 6.0, 0.05);
+        assert_close(filter_gain_db(boost, 1000.0, 1.0, 50.0), 0.0, 0.2);
+
+        let low_shelf = FilterType::LowShelf { gain_db: -6.0 };
+        assert_close(filter_gain_db(low_shelf, 500.0, BUTTERWORTH_Q, 30.0), -6.0, 0.1);
+        assert_close(filter_gain_db(low_shelf, 500.0, BUTTERWORTH_Q, 15_000.0), 0.0, 0.1);
+
+        let high_shelf = FilterType::HighShelf { gain_db: 6.0 };
+        assert_close(filter_gain_db(high_shelf, 2000.0, BUTTERWORTH_Q, 18_000.0), 6.0, 0.2);
+        assert_close(filter_gain_db(high_shelf, 2000.0, BUTTERWORTH_Q, 50.0), 0.0, 0.1);
+    }
+
+    #[test]
+    fn identity_coefficients_pass_the_signal_through() {
+        let mut filter = Biquad::default();
+        for x in [0.5, -0.25, 1.0, 0.0] {
+            assert_eq!(filter.process(x), x);
+        }
+    }
+
+    #[test]
+    fn out_of_range_parameters_still_give_a_stable_filter() {
+        let mut filter = Biquad::with_type(FilterType::LowPass, SAMPLE_RATE, 100_000.0, 0.0);
+        let mut output = 0.0;
+        for n in 0..10_000 {
+            output = filter.process(if n % 2 == 0 { 1.0 } else { -1.0 });
+        }
+        assert!(output.is_finite() && output.abs() < 10.0);
+    }
+}
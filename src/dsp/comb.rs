@@ -0,0 +1,105 @@
+/*
+Comb filters add a delayed copy of a signal to itself, which produces evenly spaced peaks or
+notches in the spectrum.
+The feedback comb recirculates its output through the delay line; a one-pole low-pass inside
+the loop (the damping) makes high frequencies die away faster than low ones, like the air and
+walls of a room or a plucked string. It is the core of Schroeder/Freeverb reverbs, comb
+resonators and Karplus-Strong string models. The feedforward comb only mixes in one delayed
+copy and is always stable.
+ */
+use super::DelayLine;
+
+/// y[n] = x[n - D] + feedback * lowpass(y[n - D])
+#[derive(Debug, Clone)]
+pub struct FeedbackComb {
+    delay_line: DelayLine,
+    delay: f32,
+    feedback: f32,
+    damping: f32,
+    filter_state: f32,
+}
+
+impl FeedbackComb {
+    /// `delay` is in samples and may be fractional; it also sets the longest delay the comb
+    /// can be retuned to.
+    pub fn new(delay: f32, feedback: f32, damping: f32) -> Self {
+        let mut comb = FeedbackComb {
+            delay_line: DelayLine::new(delay.ceil() as usize + 1),
+            delay: 1.0,
+            feedback: 0.0,
+            damping: 0.0,
+            filter_state: 0.0,
+        };
+        comb.set_delay(delay);
+        comb.set_feedback(feedback);
+        comb.set_damping(damping);
+        comb
+    }
+
+    pub fn set_delay(&mut self, delay: f32) {
+        self.delay = delay.clamp(1.0, self.delay_line.max_delay() as f32);
+    }
+
+    /// Loop gain; kept below 1 so the comb cannot blow up.
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback.clamp(-0.999, 0.999);
+    }
+
+    /// 0 leaves the loop unfiltered, values towards 1 darken the decay more and more.
+    pub fn set_damping(&mut self, damping: f32) {
+        self.damping = damping.clamp(0.0, 0.999);
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = self.delay_line.read_interpolated(self.delay);
+        self.filter_state = output * (1.0 - self.damping) + self.filter_state * self.damping;
+        if self.filter_state.abs() < 1e-20 {
+            self.filter_state = 0.0;
+        }
+        self.delay_line.write(input + self.filter_state * self.feedback);
+        output
+    }
+
+    pub fn reset(&mut self) {
+        self.delay_line.clear();
+        self.filter_state = 0.0;
+    }
+}
+
+/// y[n] = x[n] + gain * x[n - D]
+#[derive(Debug, Clone)]
+pub struct FeedforwardComb {
+    delay_line: DelayLine,
+    delay: f32,
+    gain: f32,
+}
+
+impl FeedforwardComb {
+    pub fn new(delay: f32, gain: f32) -> Self {
+        let mut comb = FeedforwardComb {
+            delay_line: DelayLine::new(delay.ceil() as usize + 1),
+            delay: 1.0,
+            gain,
+        };
+        comb.set_delay(delay);
+        comb
+    }
+
+    pub fn set_delay(&mut self, delay: f32) {
+        self.delay = delay.clamp(1.0, self.delay_line.max_delay() as f32);
+    }
+
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.delay_line.read_interpolated(self.delay);
+        self.delay_line.write(input);
+        input + self.gain * delayed
+    }
+
+    pub fn reset(&mut self) {
+        self.delay_line.clear();
+    }
+}
@@ -0,0 +1,243 @@
+/*
+Linkwitz-Riley crossovers split a signal into bands that add back up to a flat magnitude
+response.
+A 4th-order crossover cascades two 2nd-order Butterworth sections (Q = 0.707) for each side,
+and its low and high outputs sum to a 2nd-order allpass. A 2nd-order crossover uses a single
+section with Q = 0.5 per side; its high output is inverted so the sum becomes a 1st-order
+allpass instead of having a notch at the crossover frequency.
+With more than two bands, each band is also passed through the allpass of every crossover above
+it, so all bands carry the same phase shift and still sum flat.
+ */
+use super::{Biquad, Coefficients, FilterType, FirstOrderAllpass};
+
+const BUTTERWORTH_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrossoverOrder {
+    /// 12 dB per octave slopes.
+    Second,
+    /// 24 dB per octave slopes.
+    Fourth,
+}
+
+/// A two-way Linkwitz-Riley band split.
+#[derive(Debug, Clone)]
+pub struct Crossover {
+    sample_rate: f32,
+    order: CrossoverOrder,
+    low_pass: [Biquad; 2],
+    high_pass: [Biquad; 2],
+}
+
+impl Crossover {
+    pub fn new(sample_rate: f32, frequency: f32, order: CrossoverOrder) -> Self {
+        let mut crossover = Crossover {
+            sample_rate,
+            order,
+            low_pass: Default::default(),
+            high_pass: Default::default(),
+        };
+        crossover.set_frequency(frequency);
+        crossover
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        let q = match self.order {
+            CrossoverOrder::Second => 0.5,
+            CrossoverOrder::Fourth => BUTTERWORTH_Q,
+        };
+        for section in self.low_pass.iter_mut() {
+            section.set_coefficients(Coefficients::new(FilterType::LowPass, self.sample_rate, frequency, q));
+        }
+        for section in self.high_pass.iter_mut() {
+            section.set_coefficients(Coefficients::new(FilterType::HighPass, self.sample_rate, frequency, q));
+        }
+    }
+
+    /// Returns the `(low, high)` bands for one input sample.
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        match self.order {
+            CrossoverOrder::Second => {
+                let low = self.low_pass[0].process(input);
+                let high = -self.high_pass[0].process(input);
+                (low, high)
+            }
+            CrossoverOrder::Fourth => {
+                let low = self.low_pass[0].process(input);
+                let high = self.high_pass[0].process(input);
+                (self.low_pass[1].process(low), self.high_pass[1].process(high))
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for section in self.low_pass.iter_mut().chain(self.high_pass.iter_mut()) {
+            section.reset();
+        }
+    }
+}
+
+/// The allpass that a crossover's summed output is equal to, used to phase-align other bands.
+#[derive(Debug, Clone)]
+enum PhaseCompensation {
+    Second(FirstOrderAllpass),
+    Fourth(Biquad),
+}
+
+impl PhaseCompensation {
+    fn new(sample_rate: f32, frequency: f32, order: CrossoverOrder) -> Self {
+        match order {
+            CrossoverOrder::Second => {
+                PhaseCompensation::Second(FirstOrderAllpass::with_frequency(frequency, sample_rate))
+            }
+            CrossoverOrder::Fourth => PhaseCompensation::Fourth(Biquad::with_type(
+                FilterType::AllPass,
+                sample_rate,
+                frequency,
+                BUTTERWORTH_Q,
+            )),
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        match self {
+            PhaseCompensation::Second(allpass) => allpass.process(input),
+            PhaseCompensation::Fourth(allpass) => allpass.process(input),
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            PhaseCompensation::Second(allpass) => allpass.reset(),
+            PhaseCompensation::Fourth(allpass) => allpass.reset(),
+        }
+    }
+}
+
+/// Splits a signal into `frequencies.len() + 1` phase-aligned bands, lowest band first.
+#[derive(Debug, Clone)]
+pub struct MultibandSplitter {
+    crossovers: Vec<Crossover>,
+    // compensation[band] holds the allpasses of every crossover above that band
+    compensation: Vec<Vec<PhaseCompensation>>,
+}
+
+impl MultibandSplitter {
+    /// `frequencies` are the crossover points and are sorted into ascending order.
+    pub fn new(sample_rate: f32, frequencies: &[f32], order: CrossoverOrder) -> Self {
+        let mut frequencies = frequencies.to_vec();
+        frequencies.sort_by(|a, b| a.total_cmp(b));
+
+        let crossovers = frequencies
+            .iter()
+            .map(|&frequency| Crossover::new(sample_rate, frequency, order))
+            .collect();
+        let compensation = (0..frequencies.len())
+            .map(|band| {
+                frequencies[band + 1..]
+                    .iter()
+                    .map(|&frequency| PhaseCompensation::new(sample_rate, frequency, order))
+                    .collect()
+            })
+            .collect();
+
+        MultibandSplitter {
+            crossovers,
+            compensation,
+        }
+    }
+
+    pub fn num_bands(&self) -> usize {
+        self.crossovers.len() + 1
+    }
+
+    /// Splits one sample into `bands`, which must hold `num_bands()` values.
+    pub fn process(&mut self, input: f32, bands: &mut [f32]) {
+        let mut rest = input;
+        for (band, crossover) in self.crossovers.iter_mut().enumerate() {
+            let (low, high) = crossover.process(rest);
+            bands[band] = self.compensation[band]
+                .iter_mut()
+                .fold(low, |sample, allpass| allpass.process(sample));
+            rest = high;
+        }
+        bands[self.crossovers.len()] = rest;
+    }
+
+    pub fn reset(&mut self) {
+        for crossover in self.crossovers.iter_mut() {
+            crossover.reset();
+        }
+        for allpass in self.compensation.iter_mut().flatten() {
+            allpass.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::test_util::{assert_close, gain_db, sine, SAMPLE_RATE};
+
+    #[test]
+    fn fourth_order_bands_are_6_db_down_at_the_crossover() {
+        for pick in [0, 1] {
+            let mut crossover = Crossover::new(SAMPLE_RATE, 1000.0, CrossoverOrder::Fourth);
+            let gain = gain_db(1000.0, |x| {
+                let (low, high) = crossover.process(x);
+                [low, high][pick]
+            });
+            assert_close(gain, -6.02, 0.05);
+        }
+    }
+
+    #[test]
+    fn fourth_order_slopes_are_24_db_per_octave() {
+        let mut crossover = Crossover::new(SAMPLE_RATE, 500.0, CrossoverOrder::Fourth);
+        // Two octaves above the crossover the low band is about 48 dB down
+        let gain = gain_db(2000.0, |x| crossover.process(x).0);
+        assert!((-52.0..-44.0).contains(&gain), "got {}", gain);
+    }
+
+    #[test]
+    fn bands_sum_flat_for_both_orders() {
+        for order in [CrossoverOrder::Second, CrossoverOrder::Fourth] {
+            for frequency in [50.0, 400.0, 1000.0, 2500.0, 12_000.0] {
+                let mut crossover = Crossover::new(SAMPLE_RATE, 1000.0, order);
+                let gain = gain_db(frequency, |x| {
+                    let (low, high) = crossover.process(x);
+                    low + high
+                });
+                assert_close(gain, 0.0, 0.05);
+            }
+        }
+    }
+
+    #[test]
+    fn fourth_order_sum_is_the_matching_allpass() {
+        let mut crossover = Crossover::new(SAMPLE_RATE, 1000.0, CrossoverOrder::Fourth);
+        let mut allpass = Biquad::with_type(FilterType::AllPass, SAMPLE_RATE, 1000.0, BUTTERWORTH_Q);
+        for n in 0..4800 {
+            let x = if n == 0 { 1.0 } else { 0.3 * sine(440.0, n) };
+            let (low, high) = crossover.process(x);
+            assert_close(low + high, allpass.process(x), 1e-5);
+        }
+    }
+
+    #[test]
+    fn multiband_bands_sum_flat() {
+        for order in [CrossoverOrder::Second, CrossoverOrder::Fourth] {
+            let mut splitter = MultibandSplitter::new(SAMPLE_RATE, &[2000.0, 200.0, 8000.0], order);
+            assert_eq!(splitter.num_bands(), 4);
+            let mut bands = [0.0; 4];
+            for frequency in [60.0, 200.0, 700.0, 2000.0, 5000.0, 15_000.0] {
+                splitter.reset();
+                let gain = gain_db(frequency, |x| {
+                    splitter.process(x, &mut bands);
+                    bands.iter().sum()
+                });
+                assert_close(gain, 0.0, 0.05);
+            }
+        }
+    }
+}
@@ -0,0 +1,184 @@
+/*
+A circular delay buffer.
+The capacity is rounded up to a power of two so the read and write positions wrap with a bit
+mask instead of a modulo. Reads are done before writing the current sample: `read(d)` then
+returns the input from `d` samples ago, so a delay of `d` is valid from 1 up to `max_delay()`.
+Fractional delays can be read with linear interpolation, or with 4-point Hermite interpolation,
+which keeps much more of the top end when the delay is being modulated (chorus, vibrato).
+Block-based effects can write and read whole slices at once: the copies are split at most once
+where the buffer wraps, instead of masking every sample. A block read must reach back at least
+its own length, so every sample it returns was written before the block.
+ */
+
+/// How fractional delays are read from a `DelayLine`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    Hermite,
+}
+
+#[derive(Debug, Clone)]
+pub struct DelayLine {
+    buffer: Vec<f32>,
+    mask: usize,
+    write_pos: usize,
+}
+
+impl DelayLine {
+    /// Creates a delay line able to delay by at least `max_delay` samples, including the
+    /// extra samples fractional reads need around the requested position.
+    pub fn new(max_delay: usize) -> Self {
+        let capacity = (max_delay + 4).next_power_of_two();
+        DelayLine {
+            buffer: vec![0.0; capacity],
+            mask: capacity - 1,
+            write_pos: 0,
+        }
+    }
+
+    /// The longest delay, in samples, that every read mode can reach.
+    pub fn max_delay(&self) -> usize {
+        self.buffer.len() - 3
+    }
+
+    pub fn write(&mut self, input: f32) {
+        self.buffer[self.write_pos] = input;
+        self.write_pos = (self.write_pos + 1) & self.mask;
+    }
+
+    /// Writes a block of samples, oldest first, as if by calling `write` for each.
+    pub fn write_block(&mut self, input: &[f32]) {
+        // Only the newest samples survive a block longer than the buffer
+        let input = &input[input.len().saturating_sub(self.buffer.len())..];
+        let first = input.len().min(self.buffer.len() - self.write_pos);
+        self.buffer[self.write_pos..self.write_pos + first].copy_from_slice(&input[..first]);
+        self.buffer[..input.len() - first].copy_from_slice(&input[first..]);
+        self.write_pos = (self.write_pos + input.len()) & self.mask;
+    }
+
+    /// Fills `output` with the block that starts `delay` samples ago, oldest first: the same
+    /// samples per-sample `read(delay)` calls would return while the block is being written.
+    /// The delay is clamped to between the block length and `max_delay()`.
+    pub fn read_block(&self, delay: usize, output: &mut [f32]) {
+        assert!(output.len() <= self.max_delay(), "block longer than the delay line");
+        let delay = delay.clamp(output.len().max(1), self.max_delay());
+        let start = self.write_pos.wrapping_sub(delay) & self.mask;
+        let first = output.len().min(self.buffer.len() - start);
+        let (head, tail) = output.split_at_mut(first);
+        head.copy_from_slice(&self.buffer[start..start + first]);
+        tail.copy_from_slice(&self.buffer[..tail.len()]);
+    }
+
+    /// Returns the sample written `delay` samples ago, clamped to at least 1.
+    pub fn read(&self, delay: usize) -> f32 {
+        let delay = delay.clamp(1, self.buffer.len() - 1);
+        self.buffer[self.write_pos.wrapping_sub(delay) & self.mask]
+    }
+
+    /// Reads a fractional delay with linear interpolation between the neighbouring samples.
+    pub fn read_interpolated(&self, delay: f32) -> f32 {
+        let delay = delay.clamp(1.0, self.max_delay() as f32);
+        let whole = delay.floor();
+        let fraction = delay - whole;
+        let a = self.read(whole as usize);
+        let b = self.read(whole as usize + 1);
+        a + (b - a) * fraction
+    }
+
+    /// Reads a fractional delay with 4-point, 3rd-order Hermite interpolation. Delays below 2
+    /// samples are clamped, since the newer neighbour must already have been written.
+    pub fn read_hermite(&self, delay: f32) -> f32 {
+        let delay = delay.clamp(2.0, self.max_delay() as f32);
+        let whole = delay.floor() as usize;
+        let t = delay.fract();
+
+        let newer = self.read(whole - 1);
+        let y0 = self.read(whole);
+        let y1 = self.read(whole + 1);
+        let older = self.read(whole + 2);
+
+        let c1 = 0.5 * (y1 - newer);
+        let c2 = newer - 2.5 * y0 + 2.0 * y1 - 0.5 * older;
+        let c3 = 0.5 * (older - newer) + 1.5 * (y0 - y1);
+        ((c3 * t + c2) * t + c1) * t + y0
+    }
+
+    /// Reads a fractional delay with the given interpolation.
+    pub fn read_with(&self, delay: f32, interpolation: Interpolation) -> f32 {
+        match interpolation {
+            Interpolation::Linear => self.read_interpolated(delay),
+            Interpolation::Hermite => self.read_hermite(delay),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.fill(0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_returns_the_sample_from_delay_samples_ago() {
+        let mut line = DelayLine::new(8);
+        for n in 0..20 {
+            if n >= 5 {
+                assert_eq!(line.read(5), (n - 5) as f32);
+            }
+            line.write(n as f32);
+        }
+    }
+
+    #[test]
+    fn capacity_is_a_power_of_two_covering_max_delay() {
+        let line = DelayLine::new(100);
+        assert!(line.buffer.len().is_power_of_two());
+        assert!(line.max_delay() >= 100);
+    }
+
+    #[test]
+    fn fractional_reads_interpolate_between_neighbours() {
+        let mut line = DelayLine::new(8);
+        for n in 0..8 {
+            line.write(n as f32);
+        }
+        // A ramp is reproduced exactly by both interpolators
+        assert!((line.read_interpolated(2.25) - 5.75).abs() < 1e-6);
+        assert!((line.read_hermite(3.5) - 4.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn block_io_matches_per_sample_io_across_the_wrap() {
+        let input: Vec<f32> = (0..200).map(|n| (n as f32 * 0.37).sin()).collect();
+        let mut per_sample = DelayLine::new(48);
+        let mut blocks = DelayLine::new(48);
+        let delay = 40;
+
+        let mut expected = Vec::new();
+        for &x in &input {
+            expected.push(per_sample.read(delay));
+            per_sample.write(x);
+        }
+
+        let mut actual = Vec::new();
+        for chunk in input.chunks(24) {
+            let mut output = vec![0.0; chunk.len()];
+            blocks.read_block(delay, &mut output);
+            blocks.write_block(chunk);
+            actual.extend(output);
+        }
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn a_block_longer_than_the_buffer_keeps_the_newest_samples() {
+        let mut line = DelayLine::new(4);
+        let input: Vec<f32> = (0..100).map(|n| n as f32).collect();
+        line.write_block(&input);
+        assert_eq!(line.read(1), 99.0);
+        assert_eq!(line.read(3), 97.0);
+    }
+}
@@ -0,0 +1,117 @@
+/*
+Early reflections: the first distinct echoes from the walls, floor and ceiling, which arrive
+before the reflections pile up into a diffuse tail and carry most of the sense of room size.
+They are modelled with a tapped delay line: every tap is one reflection, with its own delay and
+gain. The patterns differ in spacing: a room has short, closely spaced reflections, a hall sparse
+ones spread over a longer time, and a plate a dense burst right after the direct sound. The size
+scales all tap times. Alternate taps are louder on alternate channels, so the reflections come
+from both sides. The output is scaled so that uncorrelated input keeps its power.
+ */
+use super::DelayLine;
+
+const SIZE_RANGE: (f32, f32) = (0.25, 2.0);
+const ALTERNATE_TAP_GAIN: f32 = 0.6; // gain of a tap on the side it is panned away from
+
+// (delay in ms, gain) for every reflection
+const ROOM_TAPS: [(f32, f32); 8] = [
+    (4.3, 0.84),
+    (7.1, 0.70),
+    (10.6, 0.62),
+    (13.9, 0.55),
+    (17.2, 0.47),
+    (21.5, 0.40),
+    (25.8, 0.33),
+    (31.1, 0.27),
+];
+const HALL_TAPS: [(f32, f32); 8] = [
+    (11.0, 0.80),
+    (19.0, 0.68),
+    (27.0, 0.60),
+    (37.0, 0.52),
+    (48.0, 0.45),
+    (61.0, 0.38),
+    (77.0, 0.30),
+    (95.0, 0.24),
+];
+const PLATE_TAPS: [(f32, f32); 8] = [
+    (1.2, 0.90),
+    (2.5, 0.82),
+    (3.7, 0.75),
+    (5.1, 0.68),
+    (6.6, 0.60),
+    (8.2, 0.52),
+    (9.9, 0.45),
+    (11.7, 0.38),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReflectionPattern {
+    #[default]
+    Room,
+    Hall,
+    Plate,
+}
+
+impl ReflectionPattern {
+    fn taps(self) -> &'static [(f32, f32)] {
+        match self {
+            ReflectionPattern::Room => &ROOM_TAPS,
+            ReflectionPattern::Hall => &HALL_TAPS,
+            ReflectionPattern::Plate => &PLATE_TAPS,
+        }
+    }
+
+    /// Time of the last reflection in ms, at size 1.
+    pub fn length_ms(self) -> f32 {
+        self.taps().iter().map(|&(time_ms, _)| time_ms).fold(0.0, f32::max)
+    }
+}
+
+/// A tapped delay line producing the early reflections of one channel.
+#[derive(Debug, Clone)]
+pub struct EarlyReflections {
+    delay_line: DelayLine,
+    // Delay in samples and gain of every tap
+    taps: Vec<(f32, f32)>,
+}
+
+impl EarlyReflections {
+    /// `size` scales the tap times and is clamped to 0.25-2; `channel` picks which taps are
+    /// louder.
+    pub fn new(sample_rate: f32, pattern: ReflectionPattern, size: f32, channel: usize) -> Self {
+        let size = size.clamp(SIZE_RANGE.0, SIZE_RANGE.1);
+        let mut taps: Vec<(f32, f32)> = pattern
+            .taps()
+            .iter()
+            .enumerate()
+            .map(|(k, &(time_ms, gain))| {
+                let delay = (time_ms * size / 1000.0 * sample_rate).max(1.0);
+                let side_gain = if (k + channel).is_multiple_of(2) { 1.0 } else { ALTERNATE_TAP_GAIN };
+                (delay, gain * side_gain)
+            })
+            .collect();
+        let power: f32 = taps.iter().map(|&(_, gain)| gain * gain).sum();
+        let normalization = 1.0 / power.sqrt();
+        taps.iter_mut().for_each(|(_, gain)| *gain *= normalization);
+
+        let max_delay = taps.iter().map(|&(delay, _)| delay.ceil() as usize).max().unwrap_or(1);
+        EarlyReflections {
+            delay_line: DelayLine::new(max_delay + 1),
+            taps,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = self
+            .taps
+            .iter()
+            .map(|&(delay, gain)| gain * self.delay_line.read_interpolated(delay))
+            .sum();
+        self.delay_line.write(input);
+        output
+    }
+
+    pub fn reset(&mut self) {
+        self.delay_line.clear();
+    }
+}
@@ -0,0 +1,196 @@
+/*
+Envelope detection follows the level of a signal over time.
+The detector first measures the instantaneous level, either the absolute sample value (peak)
+or a running mean square over a short window (RMS), and converts it to decibels. The level in dB
+is then smoothed with separate one-pole attack and release time constants. Working in the dB
+domain makes the ballistics behave the same at every level, which is what dynamics processors
+expect: a 10 dB drop takes just as long to release at -40 dBFS as at -6 dBFS.
+ */
+
+/// Levels are floored here, so silence does not drive the envelope towards minus infinity.
+pub const MIN_LEVEL_DB: f32 = -120.0;
+const DEFAULT_RMS_WINDOW_MS: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectionMode {
+    Peak,
+    Rms,
+}
+
+/// Returns the one-pole smoothing coefficient for a time constant in milliseconds.
+pub fn time_constant(time_ms: f32, sample_rate: f32) -> f32 {
+    if time_ms <= 0.0 {
+        0.0
+    } else {
+        (-1.0 / (time_ms * 0.001 * sample_rate)).exp()
+    }
+}
+
+pub fn linear_to_db(linear: f32) -> f32 {
+    (20.0 * linear.abs().log10()).max(MIN_LEVEL_DB)
+}
+
+pub fn db_to_linear(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+#[derive(Debug, Clone)]
+pub struct EnvelopeDetector {
+    sample_rate: f32,
+    mode: DetectionMode,
+    attack_coeff: f32,
+    release_coeff: f32,
+    rms_coeff: f32,
+    mean_square: f32,
+    envelope_db: f32,
+}
+
+impl EnvelopeDetector {
+    pub fn new(sample_rate: f32, attack_ms: f32, release_ms: f32, mode: DetectionMode) -> Self {
+        EnvelopeDetector {
+            sample_rate,
+            mode,
+            attack_coeff: time_constant(attack_ms, sample_rate),
+            release_coeff: time_constant(release_ms, sample_rate),
+            rms_coeff: time_constant(DEFAULT_RMS_WINDOW_MS, sample_rate),
+            mean_square: 0.0,
+            envelope_db: MIN_LEVEL_DB,
+        }
+    }
+
+    pub fn set_attack_ms(&mut self, attack_ms: f32) {
+        self.attack_coeff = time_constant(attack_ms, self.sample_rate);
+    }
+
+    pub fn set_release_ms(&mut self, release_ms: f32) {
+        self.release_coeff = time_constant(release_ms, self.sample_rate);
+    }
+
+    pub fn set_mode(&mut self, mode: DetectionMode) {
+        self.mode = mode;
+    }
+
+    /// Sets the averaging window used in RMS mode.
+    pub fn set_rms_window_ms(&mut self, window_ms: f32) {
+        self.rms_coeff = time_constant(window_ms, self.sample_rate);
+    }
+
+    /// Feeds one sample and returns the smoothed envelope in dB.
+    pub fn process(&mut self, input: f32) -> f32 {
+        let level_db = match self.mode {
+            DetectionMode::Peak => linear_to_db(input),
+            DetectionMode::Rms => {
+                self.mean_square = self.rms_coeff * self.mean_square + (1.0 - self.rms_coeff) * input * input;
+                linear_to_db(self.mean_square.sqrt())
+            }
+        };
+
+        let coeff = if level_db > self.envelope_db {
+            self.attack_coeff
+        } else {
+            self.release_coeff
+        };
+        self.envelope_db = coeff * self.envelope_db + (1.0 - coeff) * level_db;
+        self.envelope_db
+    }
+
+    /// The current envelope in dB.
+    pub fn level_db(&self) -> f32 {
+        self.envelope_db
+    }
+
+    /// The current envelope as a linear amplitude.
+    pub fn level(&self) -> f32 {
+        db_to_linear(self.envelope_db)
+    }
+
+    pub fn reset(&mut self) {
+        self.mean_square = 0.0;
+        self.envelope_db = MIN_LEVEL_DB;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::test_util::{assert_close, SAMPLE_RATE};
+
+    #[test]
+    fn time_constant_reaches_one_over_e_after_the_time() {
+        let coeff = time_constant(10.0, SAMPLE_RATE);
+        assert_close(coeff.powf(480.0), (-1.0_f32).exp(), 1e-4);
+        assert_eq!(time_constant(0.0, SAMPLE_RATE), 0.0);
+    }
+
+    #[test]
+    fn db_conversions_round_trip_and_floor_silence() {
+        assert_close(linear_to_db(0.5), -6.0206, 1e-3);
+        assert_close(db_to_linear(-6.0206), 0.5, 1e-4);
+        assert_eq!(linear_to_db(0.0), MIN_LEVEL_DB);
+        assert_eq!(linear_to_db(-1.0), 0.0);
+    }
+
+    #[test]
+    fn peak_attack_follows_the_one_pole_step_response() {
+        let mut detector = EnvelopeDetector::new(SAMPLE_RATE, 5.0, 50.0, DetectionMode::Peak);
+        let attack = time_constant(5.0, SAMPLE_RATE);
+        let target = linear_to_db(0.5);
+        for n in 1..=2000 {
+            let envelope = detector.process(0.5);
+            if n % 100 == 0 {
+                let expected = target + (MIN_LEVEL_DB - target) * attack.powi(n);
+                assert_close(envelope, expected, 1e-2);
+            }
+        }
+    }
+
+    #[test]
+    fn peak_release_follows_the_one_pole_step_response() {
+        let mut detector = EnvelopeDetector::new(SAMPLE_RATE, 0.0, 50.0, DetectionMode::Peak);
+        let release = time_constant(50.0, SAMPLE_RATE);
+        // With no attack smoothing the envelope jumps straight to the level
+        assert_close(detector.process(1.0), 0.0, 1e-6);
+        for n in 1..=5000 {
+            let envelope = detector.process(0.0);
+            if n % 500 == 0 {
+                let expected = MIN_LEVEL_DB * (1.0 - release.powi(n));
+                assert_close(envelope, expected, 1e-2);
+            }
+        }
+    }
+
+    #[test]
+    fn rms_window_follows_the_mean_square_step_response() {
+        let mut detector = EnvelopeDetector::new(SAMPLE_RATE, 0.0, 0.0, DetectionMode::Rms);
+        let window = time_constant(DEFAULT_RMS_WINDOW_MS, SAMPLE_RATE);
+        for n in 1..=4800 {
+            // A square wave has the same RMS level as its amplitude
+            let input = if n % 2 == 0 { 0.5 } else { -0.5 };
+            let envelope = detector.process(input);
+            if n % 480 == 0 {
+                let mean_square = 0.25 * (1.0 - window.powi(n));
+                assert_close(envelope, 10.0 * mean_square.log10(), 1e-2);
+            }
+        }
+        assert_close(detector.level(), 0.5, 1e-3);
+    }
+
+    #[test]
+    fn rms_of_a_sine_settles_3_db_below_its_peak() {
+        let mut detector = EnvelopeDetector::new(SAMPLE_RATE, 1.0, 1.0, DetectionMode::Rms);
+        detector.set_rms_window_ms(50.0);
+        let mut envelope = 0.0;
+        for n in 0..SAMPLE_RATE as usize {
+            envelope = detector.process((2.0 * std::f32::consts::PI * 1000.0 * n as f32 / SAMPLE_RATE).sin());
+        }
+        assert_close(envelope, -3.0103, 0.05);
+    }
+
+    #[test]
+    fn reset_returns_to_the_floor() {
+        let mut detector = EnvelopeDetector::new(SAMPLE_RATE, 1.0, 1.0, DetectionMode::Peak);
+        detector.process(1.0);
+        detector.reset();
+        assert_eq!(detector.level_db(), MIN_LEVEL_DB);
+    }
+}
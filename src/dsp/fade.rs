@@ -0,0 +1,134 @@
+/*
+Fade curves and crossfades.
+A curve maps the position through a fade, from 0 to 1, to a gain; a fade-out uses the same curve
+run backwards. Linear fades keep the amplitudes summing to 1, which suits correlated material
+(e.g. the same take on both sides of an edit). Equal-power fades (sine/cosine) keep the summed
+power constant, which avoids the mid-fade dip when the two signals are unrelated. The S-curve
+(raised cosine) starts and ends gently and also serves as a grain window.
+Buffers are interleaved: gains are applied per frame so all channels fade together.
+ */
+use std::f32::consts::{FRAC_PI_2, PI};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FadeCurve {
+    Linear,
+    #[default]
+    EqualPower,
+    SCurve,
+}
+
+impl FadeCurve {
+    /// Fade-in gain at `position` in [0, 1]; for a fade-out use `gain(1.0 - position)`.
+    pub fn gain(&self, position: f32) -> f32 {
+        let position = position.clamp(0.0, 1.0);
+        match self {
+            FadeCurve::Linear => position,
+            FadeCurve::EqualPower => (position * FRAC_PI_2).sin(),
+            FadeCurve::SCurve => 0.5 - 0.5 * (position * PI).cos(),
+        }
+    }
+}
+
+/// Fades in the first `length` frames of an interleaved buffer.
+pub fn fade_in(samples: &mut [f32], num_channels: usize, length: usize, curve: FadeCurve) {
+    let num_channels = num_channels.max(1);
+    for (frame_index, frame) in samples.chunks_mut(num_channels).take(length).enumerate() {
+        let gain = curve.gain(frame_index as f32 / length as f32);
+        for sample in frame.iter_mut() {
+            *sample *= gain;
+        }
+    }
+}
+
+/// Fades out the last `length` frames of an interleaved buffer, reaching silence on the last one.
+pub fn fade_out(samples: &mut [f32], num_channels: usize, length: usize, curve: FadeCurve) {
+    let num_channels = num_channels.max(1);
+    let num_frames = samples.len() / num_channels;
+    let length = length.min(num_frames);
+    let start = (num_frames - length) * num_channels;
+
+    for (frame_index, frame) in samples[start..].chunks_mut(num_channels).enumerate() {
+        let gain = curve.gain(1.0 - (frame_index + 1) as f32 / length as f32);
+        for sample in frame.iter_mut() {
+            *sample *= gain;
+        }
+    }
+}
+
+/// Crossfades from `from` into `to` over the frames they share, returning the mixed overlap.
+pub fn crossfade(from: &[f32], to: &[f32], num_channels: usize, curve: FadeCurve) -> Vec<f32> {
+    let num_channels = num_channels.max(1);
+    let length = from.len().min(to.len()) / num_channels;
+
+    from.chunks(num_channels)
+        .zip(to.chunks(num_channels))
+        .take(length)
+        .enumerate()
+        .flat_map(|(frame_index, (from_frame, to_frame))| {
+            let position = (frame_index as f32 + 0.5) / length as f32;
+            let (gain_out, gain_in) = (curve.gain(1.0 - position), curve.gain(position));
+            from_frame
+                .iter()
+                .zip(to_frame)
+                .map(move |(a, b)| a * gain_out + b * gain_in)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const CURVES: [FadeCurve; 3] = [FadeCurve::Linear, FadeCurve::EqualPower, FadeCurve::SCurve];
+
+    fn curve() -> impl Strategy<Value = FadeCurve> {
+        proptest::sample::select(CURVES.to_vec())
+    }
+
+    #[test]
+    fn curves_run_from_silence_to_unity() {
+        for curve in CURVES {
+            assert_eq!(curve.gain(0.0), 0.0);
+            assert!((curve.gain(1.0) - 1.0).abs() < 1e-6);
+            assert!((curve.gain(-1.0) - curve.gain(0.0)).abs() < 1e-6);
+        }
+        assert!((FadeCurve::EqualPower.gain(0.5).powi(2) * 2.0 - 1.0).abs() < 1e-6);
+    }
+
+    proptest! {
+        #[test]
+        fn fades_are_monotonic(curve in curve(), num_channels in 1_usize..=2, frames in 1_usize..300, length in 0_usize..400) {
+            let mut faded_in = vec![0.5; frames * num_channels];
+            fade_in(&mut faded_in, num_channels, length, curve);
+            let mut faded_out = faded_in.clone();
+            faded_out.fill(0.5);
+            fade_out(&mut faded_out, num_channels, length, curve);
+
+            for channel in 0..num_channels {
+                let rising: Vec<f32> = faded_in.iter().skip(channel).step_by(num_channels).copied().collect();
+                let falling: Vec<f32> = faded_out.iter().skip(channel).step_by(num_channels).copied().collect();
+                prop_assert!(rising.windows(2).all(|pair| pair[0] <= pair[1]));
+                prop_assert!(falling.windows(2).all(|pair| pair[0] >= pair[1]));
+                prop_assert!(rising.iter().chain(&falling).all(|&s| (0.0..=0.5).contains(&s)));
+            }
+            if length > 0 {
+                prop_assert_eq!(faded_in[0], 0.0);
+                prop_assert_eq!(*faded_out.last().unwrap(), 0.0);
+            }
+            if length < frames {
+                // Frames outside the fade are untouched
+                prop_assert_eq!(*faded_in.last().unwrap(), 0.5);
+                prop_assert_eq!(faded_out[0], 0.5);
+            }
+        }
+
+        #[test]
+        fn crossfades_stay_between_their_inputs(curve in curve(), frames in 1_usize..300) {
+            let mixed = crossfade(&vec![0.25; frames], &vec![0.25; frames], 1, curve);
+            prop_assert_eq!(mixed.len(), frames);
+            // Equal-power fades sum to more than unity amplitude in the middle, by at most sqrt(2)
+            prop_assert!(mixed.iter().all(|&s| (0.25 - 1e-6..=0.25 * std::f32::consts::SQRT_2 + 1e-6).contains(&s)));
+        }
+    }
+}
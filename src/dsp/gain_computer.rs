@@ -0,0 +1,209 @@
+/*
+The gain computer turns a detected level into a gain change for dynamics processors.
+Its static curve works in decibels: in compress mode levels above the threshold are reduced by
+the ratio (an infinite ratio makes it a limiter), in expand mode levels below the threshold are
+pushed further down (a very high ratio makes it a gate). A soft knee blends smoothly into the
+curve over `knee_db` around the threshold, using the quadratic knee from Giannoulis, Massberg
+and Reiss. The resulting gain, not the level, is then smoothed with attack and release times,
+so the ballistics stay the same whichever detector feeds it.
+ */
+use super::envelope::{db_to_linear, time_constant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GainMode {
+    /// Downward compression above the threshold (compressor, limiter).
+    Compress,
+    /// Downward expansion below the threshold (expander, gate).
+    Expand,
+}
+
+#[derive(Debug, Clone)]
+pub struct GainComputer {
+    sample_rate: f32,
+    mode: GainMode,
+    threshold_db: f32,
+    ratio: f32,
+    knee_db: f32,
+    range_db: f32,
+    attack_coeff: f32,
+    release_coeff: f32,
+    gain_db: f32,
+}
+
+impl GainComputer {
+    pub fn new(sample_rate: f32, mode: GainMode, threshold_db: f32, ratio: f32) -> Self {
+        GainComputer {
+            sample_rate,
+            mode,
+            threshold_db,
+            ratio: ratio.max(1.0),
+            knee_db: 0.0,
+            range_db: f32::INFINITY,
+            attack_coeff: 0.0,
+            release_coeff: 0.0,
+            gain_db: 0.0,
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: GainMode) {
+        self.mode = mode;
+    }
+
+    pub fn set_threshold_db(&mut self, threshold_db: f32) {
+        self.threshold_db = threshold_db;
+    }
+
+    /// Ratios below 1 are treated as 1 (no gain change); `f32::INFINITY` is allowed.
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.ratio = ratio.max(1.0);
+    }
+
+    /// Width of the soft knee in dB; 0 gives a hard knee.
+    pub fn set_knee_db(&mut self, knee_db: f32) {
+        self.knee_db = knee_db.max(0.0);
+    }
+
+    /// Limits the maximum gain reduction, e.g. 40 dB for a gate that should not fully close.
+    pub fn set_range_db(&mut self, range_db: f32) {
+        self.range_db = range_db.abs();
+    }
+
+    pub fn set_attack_ms(&mut self, attack_ms: f32) {
+        self.attack_coeff = time_constant(attack_ms, self.sample_rate);
+    }
+
+    pub fn set_release_ms(&mut self, release_ms: f32) {
+        self.release_coeff = time_constant(release_ms, self.sample_rate);
+    }
+
+    /// The static curve: the gain change in dB (zero or negative) for a level in dB.
+    pub fn static_gain_db(&self, level_db: f32) -> f32 {
+        let overshoot = level_db - self.threshold_db;
+        let half_knee = self.knee_db / 2.0;
+
+        let gain_db = match self.mode {
+            GainMode::Compress => {
+                let slope = 1.0 / self.ratio - 1.0;
+                if overshoot <= -half_knee {
+                    0.0
+                } else if overshoot < half_knee {
+                    slope * (overshoot + half_knee).powi(2) / (2.0 * self.knee_db)
+                } else {
+                    slope * overshoot
+                }
+            }
+            GainMode::Expand => {
+                let slope = self.ratio - 1.0;
+                if overshoot >= half_knee {
+                    0.0
+                } else if overshoot > -half_knee {
+                    -slope * (overshoot - half_knee).powi(2) / (2.0 * self.knee_db)
+                } else {
+                    slope * overshoot
+                }
+            }
+        };
+
+        // An infinite ratio with no overshoot gives NaN (0 * inf)
+        if gain_db.is_nan() {
+            0.0
+        } else {
+            gain_db.max(-self.range_db)
+        }
+    }
+
+    /// Computes the gain for `level_db`, smooths it, and returns the smoothed gain in dB.
+    pub fn process(&mut self, level_db: f32) -> f32 {
+        let target_db = self.static_gain_db(level_db);
+        // More reduction is the attack phase, recovering towards 0 dB is the release phase
+        let coeff = if target_db < self.gain_db {
+            self.attack_coeff
+        } else {
+            self.release_coeff
+        };
+        self.gain_db = coeff * self.gain_db + (1.0 - coeff) * target_db;
+        self.gain_db
+    }
+
+    /// The current smoothed gain in dB.
+    pub fn gain_db(&self) -> f32 {
+        self.gain_db
+    }
+
+    /// The current smoothed gain as a linear factor.
+    pub fn gain(&self) -> f32 {
+        db_to_linear(self.gain_db)
+    }
+
+    pub fn reset(&mut self) {
+        self.gain_db = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::test_util::{assert_close, SAMPLE_RATE};
+
+    #[test]
+    fn compressor_reduces_the_overshoot_by_the_ratio() {
+        let computer = GainComputer::new(SAMPLE_RATE, GainMode::Compress, -20.0, 4.0);
+        assert_close(computer.static_gain_db(-10.0), -7.5, 1e-5);
+        assert_close(computer.static_gain_db(0.0), -15.0, 1e-5);
+        assert_eq!(computer.static_gain_db(-30.0), 0.0);
+    }
+
+    #[test]
+    fn infinite_ratio_limits_to_the_threshold() {
+        let computer = GainComputer::new(SAMPLE_RATE, GainMode::Compress, -6.0, f32::INFINITY);
+        assert_close(computer.static_gain_db(0.0), -6.0, 1e-5);
+        assert_eq!(computer.static_gain_db(-6.0), 0.0);
+        assert_eq!(computer.static_gain_db(-20.0), 0.0);
+    }
+
+    #[test]
+    fn expander_pushes_quiet_levels_down_within_the_range() {
+        let mut computer = GainComputer::new(SAMPLE_RATE, GainMode::Expand, -40.0, 2.0);
+        assert_close(computer.static_gain_db(-50.0), -10.0, 1e-5);
+        assert_eq!(computer.static_gain_db(-30.0), 0.0);
+
+        computer.set_ratio(100.0);
+        computer.set_range_db(-40.0);
+        assert_close(computer.static_gain_db(-60.0), -40.0, 1e-5);
+    }
+
+    #[test]
+    fn soft_knee_is_quadratic_and_meets_the_hard_curve_at_its_edges() {
+        let mut computer = GainComputer::new(SAMPLE_RATE, GainMode::Compress, -20.0, 4.0);
+        computer.set_knee_db(10.0);
+        // slope * (knee / 2)^2 / (2 * knee) at the threshold
+        assert_close(computer.static_gain_db(-20.0), -0.75 * 25.0 / 20.0, 1e-5);
+        assert_close(computer.static_gain_db(-25.0), 0.0, 1e-5);
+        assert_close(computer.static_gain_db(-15.0), -0.75 * 5.0, 1e-4);
+        assert_close(computer.static_gain_db(-14.999), -0.75 * 5.001, 1e-3);
+    }
+
+    #[test]
+    fn gain_is_smoothed_with_the_attack_and_release_times() {
+        let mut computer = GainComputer::new(SAMPLE_RATE, GainMode::Compress, -20.0, 4.0);
+        computer.set_attack_ms(10.0);
+        computer.set_release_ms(100.0);
+        let attack = time_constant(10.0, SAMPLE_RATE);
+        let release = time_constant(100.0, SAMPLE_RATE);
+
+        for n in 1..=4800 {
+            let gain = computer.process(-10.0);
+            if n % 480 == 0 {
+                assert_close(gain, -7.5 * (1.0 - attack.powi(n)), 1e-3);
+            }
+        }
+        let reduced = computer.gain_db();
+        for n in 1..=4800 {
+            let gain = computer.process(-40.0);
+            if n % 480 == 0 {
+                assert_close(gain, reduced * release.powi(n), 1e-3);
+            }
+        }
+        assert_close(computer.gain(), db_to_linear(computer.gain_db()), 1e-6);
+    }
+}
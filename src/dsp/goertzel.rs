@@ -0,0 +1,63 @@
+/*
+The Goertzel algorithm measures the energy of a single frequency with one two-state recursion
+per sample, which is much cheaper than a full FFT when only a handful of frequencies matter
+(mains hum detection, DTMF-style tone checks, test-tone verification, chroma bins).
+The frequency does not have to fall on an FFT bin. Window the block beforehand when nearby
+frequencies must be kept apart.
+ */
+use std::f32::consts::PI;
+
+#[derive(Debug, Clone)]
+pub struct Goertzel {
+    coefficient: f32,
+    s1: f32,
+    s2: f32,
+    count: usize,
+}
+
+impl Goertzel {
+    pub fn new(frequency: f32, sample_rate: f32) -> Self {
+        Goertzel {
+            coefficient: 2.0 * (2.0 * PI * frequency / sample_rate).cos(),
+            s1: 0.0,
+            s2: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) {
+        let s0 = input + self.coefficient * self.s1 - self.s2;
+        self.s2 = self.s1;
+        self.s1 = s0;
+        self.count += 1;
+    }
+
+    /// Squared magnitude of the frequency over the samples processed so far.
+    pub fn power(&self) -> f32 {
+        self.s1 * self.s1 + self.s2 * self.s2 - self.coefficient * self.s1 * self.s2
+    }
+
+    /// Estimated amplitude of a sine at the frequency (exact for whole numbers of cycles).
+    pub fn amplitude(&self) -> f32 {
+        if self.count == 0 {
+            0.0
+        } else {
+            2.0 * self.power().max(0.0).sqrt() / self.count as f32
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.s1 = 0.0;
+        self.s2 = 0.0;
+        self.count = 0;
+    }
+}
+
+/// Returns the squared magnitude of `frequency` in `block`.
+pub fn goertzel_power(block: &[f32], frequency: f32, sample_rate: f32) -> f32 {
+    let mut detector = Goertzel::new(frequency, sample_rate);
+    for &sample in block {
+        detector.process(sample);
+    }
+    detector.power()
+}
@@ -0,0 +1,173 @@
+/*
+A low-frequency oscillator for modulation effects.
+The oscillator keeps a phase accumulator in [0, 1) that advances by frequency / sample rate
+every sample, and maps the (offset) phase through the selected waveform to a bipolar value in
+[-1, 1]. The rate can be given in Hz or synced to a tempo as a number of beats per cycle.
+The sample-and-hold shape picks a new random value every time the phase wraps.
+`SmoothRandom` is a noise-shaped modulator for drift: it picks random targets at the given rate
+and glides towards them through two one-pole low-passes tuned to that rate, so it wanders like
+a worn motor instead of stepping or repeating.
+ */
+use super::noise::Rng;
+use std::f32::consts::PI;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Sine,
+    Triangle,
+    /// Rising ramp from -1 to 1.
+    Saw,
+    /// Pulse wave whose duty cycle is set by the pulse width.
+    Square,
+    /// A new random value on every cycle, held until the next one.
+    SampleAndHold,
+}
+
+/// Returns the frequency in Hz of one cycle every `beats` beats at `bpm`.
+pub fn synced_frequency(bpm: f32, beats: f32) -> f32 {
+    bpm / 60.0 / beats
+}
+
+#[derive(Debug, Clone)]
+pub struct Lfo {
+    sample_rate: f32,
+    frequency: f32,
+    waveform: Waveform,
+    phase: f32,
+    phase_offset: f32,
+    pulse_width: f32,
+    held_value: f32,
+    rng: Rng,
+}
+
+impl Lfo {
+    pub fn new(sample_rate: f32, frequency: f32, waveform: Waveform) -> Self {
+        let mut lfo = Lfo {
+            sample_rate,
+            frequency,
+            waveform,
+            phase: 0.0,
+            phase_offset: 0.0,
+            pulse_width: 0.5,
+            held_value: 0.0,
+            rng: Rng::new(0x9E37_79B9),
+        };
+        lfo.held_value = lfo.rng.next_bipolar();
+        lfo
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.frequency = frequency.max(0.0);
+    }
+
+    /// Runs one cycle every `beats` beats at `bpm`, e.g. `beats = 0.5` for eighth notes.
+    pub fn set_tempo_sync(&mut self, bpm: f32, beats: f32) {
+        self.set_frequency(synced_frequency(bpm, beats));
+    }
+
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+    }
+
+    /// Shifts the waveform by a fraction of a cycle, e.g. 0.25 for 90 degrees.
+    pub fn set_phase_offset(&mut self, phase_offset: f32) {
+        self.phase_offset = phase_offset.rem_euclid(1.0);
+    }
+
+    /// Sets the duty cycle of the square wave, clamped to [0.01, 0.99].
+    pub fn set_pulse_width(&mut self, pulse_width: f32) {
+        self.pulse_width = pulse_width.clamp(0.01, 0.99);
+    }
+
+    /// Seeds the random generator used by the sample-and-hold shape.
+    pub fn set_seed(&mut self, seed: u32) {
+        self.rng = Rng::new(seed);
+        self.held_value = self.rng.next_bipolar();
+    }
+
+    /// Restarts the cycle at phase zero.
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
+    /// Returns the current value in [-1, 1] and advances the oscillator by one sample.
+    pub fn next_value(&mut self) -> f32 {
+        let value = self.value();
+
+        self.phase += self.frequency / self.sample_rate;
+        if self.phase >= 1.0 {
+            self.phase -= self.phase.floor();
+            self.held_value = self.rng.next_bipolar();
+        }
+
+        value
+    }
+
+    /// Returns the current value in [-1, 1] without advancing.
+    pub fn value(&self) -> f32 {
+        let phase = (self.phase + self.phase_offset).fract();
+        match self.waveform {
+            Waveform::Sine => (2.0 * PI * phase).sin(),
+            // Shifted by a quarter cycle so it lines up with the sine
+            Waveform::Triangle => 1.0 - 4.0 * ((phase + 0.25).fract() - 0.5).abs(),
+            Waveform::Saw => 2.0 * phase - 1.0,
+            Waveform::Square => {
+                if phase < self.pulse_width {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::SampleAndHold => self.held_value,
+        }
+    }
+}
+
+/// Smoothly wandering random modulation, roughly within [-1, 1].
+#[derive(Debug, Clone)]
+pub struct SmoothRandom {
+    sample_rate: f32,
+    frequency: f32,
+    phase: f32,
+    coefficient: f32,
+    target: f32,
+    stages: [f32; 2],
+    rng: Rng,
+}
+
+impl SmoothRandom {
+    pub fn new(sample_rate: f32, frequency: f32, seed: u32) -> Self {
+        let mut random = SmoothRandom {
+            sample_rate,
+            frequency: 0.0,
+            phase: 0.0,
+            coefficient: 0.0,
+            target: 0.0,
+            stages: [0.0; 2],
+            rng: Rng::new(seed),
+        };
+        random.set_frequency(frequency);
+        random.target = random.rng.next_bipolar();
+        random
+    }
+
+    /// Sets how many new random targets are picked per second.
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.frequency = frequency.max(0.0);
+        self.coefficient = (-2.0 * PI * self.frequency / self.sample_rate).exp();
+    }
+
+    /// Returns the current value and advances by one sample.
+    pub fn next_value(&mut self) -> f32 {
+        self.phase += self.frequency / self.sample_rate;
+        if self.phase >= 1.0 {
+            self.phase -= self.phase.floor();
+            self.target = self.rng.next_bipolar();
+        }
+
+        let coefficient = self.coefficient;
+        self.stages[0] = coefficient * self.stages[0] + (1.0 - coefficient) * self.target;
+        self.stages[1] = coefficient * self.stages[1] + (1.0 - coefficient) * self.stages[0];
+        self.stages[1]
+    }
+}
@@ -0,0 +1,71 @@
+pub mod adsr;
+pub mod allpass;
+pub mod biquad;
+pub mod comb;
+pub mod crossover;
+pub mod delay_line;
+pub mod early_reflections;
+pub mod envelope;
+pub mod fade;
+pub mod gain_computer;
+pub mod goertzel;
+pub mod lfo;
+pub mod loudness;
+pub mod noise;
+pub mod phase_vocoder;
+pub mod resampler;
+pub mod sanitize;
+pub mod stft;
+pub mod svf;
+pub mod true_peak;
+
+pub use adsr::{Adsr, Stage};
+pub use allpass::{AllpassDelay, FirstOrderAllpass, SchroederAllpass};
+pub use biquad::{Biquad, Coefficients, FilterType};
+pub use comb::{FeedbackComb, FeedforwardComb};
+pub use crossover::{Crossover, CrossoverOrder, MultibandSplitter};
+pub use delay_line::{DelayLine, Interpolation};
+pub use early_reflections::{EarlyReflections, ReflectionPattern};
+pub use envelope::{DetectionMode, EnvelopeDetector};
+pub use fade::{crossfade, fade_in, fade_out, FadeCurve};
+pub use gain_computer::{GainComputer, GainMode};
+pub use goertzel::{goertzel_power, Goertzel};
+pub use lfo::{Lfo, SmoothRandom, Waveform};
+pub use loudness::{measure_loudness, Loudness};
+pub use noise::{BrownNoise, PinkNoise, Rng, WhiteNoise};
+pub use phase_vocoder::PhaseVocoder;
+pub use resampler::{resample, Resampler, ResamplerQuality};
+pub use sanitize::{is_finite, sanitize};
+pub use stft::{Complex, Fft, Stft};
+pub use svf::{StateVariableFilter, SvfOutput};
+pub use true_peak::{true_peak, TruePeakDetector};
+
+// Fixtures shared by the unit tests of the primitives and the effects built from them
+#[cfg(test)]
+pub(crate) mod test_util {
+    pub const SAMPLE_RATE: f32 = 48_000.0;
+
+    pub fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!((actual - expected).abs() <= tolerance, "expected {}, got {}", expected, actual);
+    }
+
+    /// Sample `n` of a unit sine at `frequency`, at `SAMPLE_RATE`.
+    pub fn sine(frequency: f32, n: usize) -> f32 {
+        (2.0 * std::f64::consts::PI * frequency as f64 * n as f64 / SAMPLE_RATE as f64).sin() as f32
+    }
+
+    /// Steady-state gain in dB of a sine through `process`: the RMS level over the second half
+    /// of one second, once filters have settled.
+    pub fn gain_db(frequency: f32, mut process: impl FnMut(f32) -> f32) -> f32 {
+        let length = SAMPLE_RATE as usize;
+        let mut sum = 0.0_f64;
+        for n in 0..length {
+            let y = process(sine(frequency, n));
+            if n >= length / 2 {
+                sum += (y as f64).powi(2);
+            }
+        }
+        let rms = (sum / (length / 2) as f64).sqrt();
+        (20.0 * (rms * std::f64::consts::SQRT_2).log10()) as f32
+    }
+}
@@ -0,0 +1,108 @@
+/*
+Noise generators for test signals, analogue-style hiss and dither.
+All generators draw from a small xorshift random number generator, so a given seed always
+produces the same noise and renders stay reproducible.
+White noise has equal energy per Hz. Pink noise falls at 3 dB per octave (equal energy per
+octave) and is made by filtering white noise with Paul Kellet's refined set of one-pole filters.
+Brown noise falls at 6 dB per octave and is leaky-integrated white noise.
+ */
+
+/// xorshift32 random number generator.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    /// A zero seed would get the generator stuck, so it is replaced by 1.
+    pub fn new(seed: u32) -> Self {
+        Rng { state: seed.max(1) }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// Uniformly distributed in [0, 1].
+    pub fn next_unipolar(&mut self) -> f32 {
+        self.next_u32() as f32 / u32::MAX as f32
+    }
+
+    /// Uniformly distributed in [-1, 1].
+    pub fn next_bipolar(&mut self) -> f32 {
+        self.next_unipolar() * 2.0 - 1.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WhiteNoise {
+    rng: Rng,
+}
+
+impl WhiteNoise {
+    pub fn new(seed: u32) -> Self {
+        WhiteNoise { rng: Rng::new(seed) }
+    }
+
+    /// Returns the next sample, uniformly distributed in [-1, 1].
+    pub fn next_sample(&mut self) -> f32 {
+        self.rng.next_bipolar()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PinkNoise {
+    rng: Rng,
+    state: [f32; 7],
+}
+
+impl PinkNoise {
+    pub fn new(seed: u32) -> Self {
+        PinkNoise {
+            rng: Rng::new(seed),
+            state: [0.0; 7],
+        }
+    }
+
+    /// Returns the next sample, roughly within [-1, 1].
+    pub fn next_sample(&mut self) -> f32 {
+        let white = self.rng.next_bipolar();
+        let b = &mut self.state;
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.96900 * b[2] + white * 0.153852;
+        b[3] = 0.86650 * b[3] + white * 0.3104856;
+        b[4] = 0.55000 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.0168980;
+        let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+        b[6] = white * 0.115926;
+        pink * 0.11
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BrownNoise {
+    rng: Rng,
+    state: f32,
+}
+
+impl BrownNoise {
+    pub fn new(seed: u32) -> Self {
+        BrownNoise {
+            rng: Rng::new(seed),
+            state: 0.0,
+        }
+    }
+
+    /// Returns the next sample, roughly within [-1, 1].
+    pub fn next_sample(&mut self) -> f32 {
+        // The leak keeps the integrator from drifting away from zero
+        self.state = (self.state + 0.02 * self.rng.next_bipolar()) / 1.02;
+        self.state * 3.5
+    }
+}
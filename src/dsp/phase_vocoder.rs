@@ -0,0 +1,179 @@
+/*
+Time stretching with a phase vocoder, built on the STFT in `stft`.
+Output frames are laid out at the STFT's fixed hop, and each one is taken from the input at the
+same position divided by the stretch factor, so the output is longer or shorter while every
+frame keeps its spectrum. Moving the frames apart breaks the phase continuity between them, so
+the phases are rebuilt: the instantaneous frequency of every bin is measured from a second
+analysis one hop earlier, and the synthesis phase advances by that frequency times the hop.
+Identity phase locking (Laroche and Dolson) only does this for spectral peaks; the bins around
+each peak keep their phase relative to it, which keeps the partials coherent and greatly reduces
+the "phasiness" of a plain phase vocoder. Transients are still smeared somewhat when stretching.
+ */
+use std::f32::consts::PI;
+
+use super::stft::{hann_window, Complex, Fft, Stft};
+
+const FRAME_SIZE: usize = 2048;
+const HOP_SIZE: usize = 512;
+
+// Wraps a phase into [-pi, pi)
+fn principal_angle(phase: f32) -> f32 {
+    phase - 2.0 * PI * ((phase + PI) / (2.0 * PI)).floor()
+}
+
+/// A phase vocoder that changes the length of a signal without changing its pitch.
+pub struct PhaseVocoder {
+    stft: Stft,
+    fft: Fft,
+    window: Vec<f32>,
+}
+
+impl Default for PhaseVocoder {
+    fn default() -> Self {
+        PhaseVocoder::new()
+    }
+}
+
+impl PhaseVocoder {
+    pub fn new() -> Self {
+        PhaseVocoder {
+            stft: Stft::new(FRAME_SIZE, HOP_SIZE),
+            fft: Fft::new(FRAME_SIZE),
+            window: hann_window(FRAME_SIZE),
+        }
+    }
+
+    // Windowed spectrum of the frame starting at `start`, with silence outside the input
+    fn spectrum_at(&self, samples: &[f32], start: isize) -> Vec<Complex<f32>> {
+        let frame: Vec<f32> = self
+            .window
+            .iter()
+            .enumerate()
+            .map(|(i, w)| {
+                let position = start + i as isize;
+                let sample = if position >= 0 {
+                    samples.get(position as usize).copied().unwrap_or(0.0)
+                } else {
+                    0.0
+                };
+                sample * w
+            })
+            .collect();
+        self.fft.forward(&frame)
+    }
+
+    /// Stretches a single channel to `stretch` times its length (rounded to whole samples).
+    pub fn process(&self, samples: &[f32], stretch: f64) -> Vec<f32> {
+        let length = (samples.len() as f64 * stretch).round() as usize;
+        let padding = FRAME_SIZE - HOP_SIZE;
+        let num_frames = (length + padding).div_ceil(HOP_SIZE);
+        let num_bins = self.fft.num_bins();
+        // Phase advance of each bin's centre frequency over one hop
+        let bin_advance: Vec<f32> = (0..num_bins).map(|bin| 2.0 * PI * (bin * HOP_SIZE) as f32 / FRAME_SIZE as f32).collect();
+
+        let mut synthesis_phase = vec![0.0_f32; num_bins];
+        let mut frames = Vec::with_capacity(num_frames);
+        for index in 0..num_frames {
+            // Line up the frame centres, so the start of the input stays at the start of the output
+            let centre = (index * HOP_SIZE) as f64 - padding as f64 + FRAME_SIZE as f64 / 2.0;
+            let start = (centre / stretch - FRAME_SIZE as f64 / 2.0).round() as isize;
+            let current = self.spectrum_at(samples, start);
+            let magnitude: Vec<f32> = current.iter().map(|value| value.norm()).collect();
+            let phase: Vec<f32> = current.iter().map(|value| value.arg()).collect();
+
+            if index == 0 {
+                synthesis_phase.copy_from_slice(&phase);
+            } else {
+                let previous = self.spectrum_at(samples, start - HOP_SIZE as isize);
+                let peaks = find_peaks(&magnitude);
+                for &peak in &peaks {
+                    let deviation = principal_angle(phase[peak] - previous[peak].arg() - bin_advance[peak]);
+                    synthesis_phase[peak] += bin_advance[peak] + deviation;
+                }
+                // Every other bin follows the phase of the peak whose region it is in
+                let mut region = 0;
+                for bin in 0..num_bins {
+                    if peaks.is_empty() {
+                        synthesis_phase[bin] = phase[bin];
+                        continue;
+                    }
+                    while region + 1 < peaks.len() && bin > (peaks[region] + peaks[region + 1]) / 2 {
+                        region += 1;
+                    }
+                    let peak = peaks[region];
+                    if bin != peak {
+                        synthesis_phase[bin] = synthesis_phase[peak] + phase[bin] - phase[peak];
+                    }
+                }
+            }
+
+            frames.push(
+                magnitude
+                    .iter()
+                    .zip(&synthesis_phase)
+                    .map(|(&norm, &phase)| Complex::from_polar(norm, principal_angle(phase)))
+                    .collect::<Vec<_>>(),
+            );
+            for phase in synthesis_phase.iter_mut() {
+                *phase = principal_angle(*phase);
+            }
+        }
+
+        self.stft.synthesize(&frames, length)
+    }
+}
+
+// Bins that are louder than their two neighbours on either side
+fn find_peaks(magnitude: &[f32]) -> Vec<usize> {
+    (0..magnitude.len())
+        .filter(|&bin| {
+            let neighbours = bin.saturating_sub(2)..(bin + 3).min(magnitude.len());
+            magnitude[bin] > 0.0 && neighbours.filter(|&other| other != bin).all(|other| magnitude[bin] > magnitude[other])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::goertzel_power;
+    use crate::dsp::test_util::{sine, SAMPLE_RATE};
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn length_follows_the_stretch() {
+        let vocoder = PhaseVocoder::new();
+        let input = vec![0.1; 10_000];
+        for stretch in [0.25, 0.5, 1.0, 1.37, 4.0_f64] {
+            let expected = (10_000.0 * stretch).round() as usize;
+            assert_eq!(vocoder.process(&input, stretch).len(), expected);
+        }
+        assert!(vocoder.process(&[], 2.0).is_empty());
+    }
+
+    #[test]
+    fn stretched_sine_keeps_its_pitch_and_level() {
+        let vocoder = PhaseVocoder::new();
+        let input: Vec<f32> = (0..SAMPLE_RATE as usize).map(|n| 0.5 * sine(440.0, n)).collect();
+        for stretch in [0.5, 1.5, 2.0] {
+            let output = vocoder.process(&input, stretch);
+            // Away from the edges, where the frames run into silence
+            let middle = &output[output.len() / 4..3 * output.len() / 4];
+            let level = rms(middle) / rms(&input);
+            assert!((level - 1.0).abs() < 0.05, "stretch {}: level {}", stretch, level);
+            let at_pitch = goertzel_power(middle, 440.0, SAMPLE_RATE);
+            for wrong_pitch in [440.0 * stretch as f32, 440.0 / stretch as f32] {
+                assert!(goertzel_power(middle, wrong_pitch, SAMPLE_RATE) < at_pitch / 1000.0);
+            }
+        }
+    }
+
+    #[test]
+    fn silence_stays_silent() {
+        let output = PhaseVocoder::new().process(&[0.0; 5000], 1.5);
+        assert!(output.iter().all(|&s| s == 0.0));
+    }
+}
@@ -0,0 +1,307 @@
+/*
+Sample rate conversion with a polyphase windowed-sinc interpolator.
+Every output sample sits at a fractional position in the input. The fractional part selects one
+of a table of precomputed filter phases (neighbouring phases are linearly interpolated, so any
+ratio works, not only simple fractions), and the filter taps are applied to the input samples
+around that position. The sinc cutoff is lowered to the output Nyquist when downsampling, so
+content that cannot be represented is filtered out instead of aliasing, and the kernel is
+lengthened by the same factor (up to 16x) so the transition band stays as narrow. A Kaiser window keeps
+the stopband clean; the quality presets trade filter length and stopband depth for speed.
+ */
+use std::f64::consts::PI;
+
+// Longest kernel growth when downsampling, as a multiple of the preset's taps per phase
+const MAX_STRETCH: f64 = 16.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResamplerQuality {
+    Fast,
+    #[default]
+    Medium,
+    High,
+}
+
+impl ResamplerQuality {
+    // (taps per phase, number of phases, Kaiser beta, passband edge as a fraction of Nyquist)
+    fn settings(&self) -> (usize, usize, f64, f64) {
+        match self {
+            ResamplerQuality::Fast => (8, 64, 5.0, 0.85),
+            ResamplerQuality::Medium => (32, 256, 8.0, 0.92),
+            ResamplerQuality::High => (64, 1024, 10.0, 0.96),
+        }
+    }
+}
+
+// Zeroth-order modified Bessel function of the first kind, used by the Kaiser window
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half_x = x / 2.0;
+    for k in 1..50 {
+        term *= (half_x / k as f64).powi(2);
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    input_rate: u32,
+    output_rate: u32,
+    ratio: f64,
+    taps: usize,
+    phases: usize,
+    // (phases + 1) rows of `taps` coefficients; the extra row lets the last phase interpolate
+    table: Vec<f32>,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32, quality: ResamplerQuality) -> Self {
+        assert!(input_rate > 0 && output_rate > 0, "sample rates must be positive");
+        let (taps, phases, beta, passband) = quality.settings();
+        let ratio = output_rate as f64 / input_rate as f64;
+        let cutoff = ratio.min(1.0) * passband;
+        // Lowering the cutoff stretches the sinc, so the kernel grows with it to keep the same
+        // number of zero crossings (and the same transition band relative to the output rate)
+        let stretch = (1.0 / ratio).clamp(1.0, MAX_STRETCH);
+        let taps = ((taps as f64 * stretch / 2.0).ceil() as usize) * 2;
+        let half = (taps / 2) as f64;
+
+        let mut table = Vec::with_capacity((phases + 1) * taps);
+        for phase in 0..=phases {
+            let fraction = phase as f64 / phases as f64;
+            let row: Vec<f64> = (0..taps)
+                .map(|k| {
+                    // Distance from the output position to input tap k
+                    let distance = k as f64 - half + 1.0 - fraction;
+                    let x = PI * cutoff * distance;
+                    let sinc = if x.abs() < 1e-9 { 1.0 } else { x.sin() / x };
+                    let normalized = distance / half;
+                    let window = if normalized.abs() >= 1.0 {
+                        0.0
+                    } else {
+                        bessel_i0(beta * (1.0 - normalized * normalized).sqrt()) / bessel_i0(beta)
+                    };
+                    sinc * window
+                })
+                .collect();
+            // Unity gain at DC for every phase
+            let sum: f64 = row.iter().sum();
+            table.extend(row.iter().map(|&c| (c / sum) as f32));
+        }
+
+        Resampler {
+            input_rate,
+            output_rate,
+            ratio,
+            taps,
+            phases,
+            table,
+        }
+    }
+
+    /// Output samples per input sample.
+    pub fn ratio(&self) -> f64 {
+        self.ratio
+    }
+
+    /// Number of output frames produced for `input_frames` input frames.
+    pub fn output_length(&self, input_frames: usize) -> usize {
+        (input_frames as u64 * self.output_rate as u64).div_ceil(self.input_rate as u64) as usize
+    }
+
+    /// Resamples a single channel.
+    pub fn process(&self, input: &[f32]) -> Vec<f32> {
+        self.process_interleaved(input, 1)
+    }
+
+    /// Resamples an interleaved buffer, keeping the channel count.
+    pub fn process_interleaved(&self, input: &[f32], num_channels: usize) -> Vec<f32> {
+        let num_channels = num_channels.max(1);
+        let input_frames = input.len() / num_channels;
+        let output_frames = self.output_length(input_frames);
+        let half = self.taps / 2;
+
+        let mut output = Vec::with_capacity(output_frames * num_channels);
+        for n in 0..output_frames {
+            let position = n as f64 / self.ratio;
+            let whole = position.floor() as isize;
+            let phase_position = (position - whole as f64) * self.phases as f64;
+            let phase = (phase_position.floor() as usize).min(self.phases - 1);
+            let blend = (phase_position - phase as f64) as f32;
+
+            let row_a = &self.table[phase * self.taps..(phase + 1) * self.taps];
+            let row_b = &self.table[(phase + 1) * self.taps..(phase + 2) * self.taps];
+
+            for channel in 0..num_channels {
+                let mut sum = 0.0;
+                for k in 0..self.taps {
+                    let index = whole - half as isize + 1 + k as isize;
+                    if index < 0 || index as usize >= input_frames {
+                        continue;
+                    }
+                    let coefficient = row_a[k] + (row_b[k] - row_a[k]) * blend;
+                    sum += input[index as usize * num_channels + channel] * coefficient;
+                }
+                output.push(sum);
+            }
+        }
+
+        output
+    }
+}
+
+/// Converts an interleaved buffer from `input_rate` to `output_rate`.
+pub fn resample(
+    input: &[f32],
+    num_channels: usize,
+    input_rate: u32,
+    output_rate: u32,
+    quality: ResamplerQuality,
+) -> Vec<f32> {
+    if input_rate == output_rate {
+        return input.to_vec();
+    }
+    Resampler::new(input_rate, output_rate, quality).process_interleaved(input, num_channels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    const QUALITIES: [ResamplerQuality; 3] = [
+        ResamplerQuality::Fast,
+        ResamplerQuality::Medium,
+        ResamplerQuality::High,
+    ];
+
+    fn sine(frequency: f64, sample_rate: u32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|i| (2.0 * PI * frequency * i as f64 / sample_rate as f64).sin() as f32)
+            .collect()
+    }
+
+    // RMS level in dB over the middle of the buffer, away from the filter's edge effects
+    fn rms_db(samples: &[f32]) -> f32 {
+        let middle = &samples[samples.len() / 4..samples.len() * 3 / 4];
+        let mean_square = middle.iter().map(|s| s * s).sum::<f32>() / middle.len() as f32;
+        10.0 * mean_square.log10()
+    }
+
+    #[test]
+    fn output_length_follows_the_rate_ratio() {
+        let up = Resampler::new(44100, 48000, ResamplerQuality::Medium);
+        assert_eq!(up.output_length(44100), 48000);
+        assert_eq!(up.output_length(1), 2);
+        assert_eq!(up.output_length(0), 0);
+
+        let down = Resampler::new(48000, 44100, ResamplerQuality::Medium);
+        assert_eq!(down.output_length(48000), 44100);
+        assert_eq!(down.output_length(1), 1);
+
+        let stereo = vec![0.0; 2 * 44100];
+        assert_eq!(up.process_interleaved(&stereo, 2).len(), 2 * 48000);
+    }
+
+    #[test]
+    fn equal_rates_pass_through_unchanged() {
+        let input = sine(1000.0, 48000, 1000);
+        assert_eq!(resample(&input, 1, 48000, 48000, ResamplerQuality::Fast), input);
+    }
+
+    #[test]
+    fn upsampled_sine_matches_the_ideal_signal() {
+        // (quality, maximum error relative to full scale in dB)
+        let limits = [-50.0, -75.0, -100.0];
+        let input = sine(1000.0, 44100, 44100);
+        let expected = sine(1000.0, 48000, 48000);
+        for (quality, limit) in QUALITIES.iter().zip(limits) {
+            let output = resample(&input, 1, 44100, 48000, *quality);
+            assert_eq!(output.len(), expected.len());
+            let error: Vec<f32> = output.iter().zip(&expected).map(|(a, b)| a - b).collect();
+            let error_db = rms_db(&error) - rms_db(&expected);
+            assert!(error_db < limit, "{:?}: error {} dB", quality, error_db);
+        }
+    }
+
+    #[test]
+    fn downsampled_sine_round_trips() {
+        let input = sine(1000.0, 48000, 48000);
+        let down = resample(&input, 1, 48000, 44100, ResamplerQuality::High);
+        assert_eq!(down.len(), 44100);
+        let up = resample(&down, 1, 44100, 48000, ResamplerQuality::High);
+        assert_eq!(up.len(), 48000);
+        let error: Vec<f32> = up.iter().zip(&input).map(|(a, b)| a - b).collect();
+        assert!(rms_db(&error) - rms_db(&input) < -90.0);
+    }
+
+    #[test]
+    fn content_above_the_output_nyquist_is_rejected() {
+        // A 20 kHz tone cannot be represented at 22.05 kHz and would otherwise fold to 2.05 kHz
+        let limits = [-45.0, -80.0, -100.0];
+        let input = sine(20000.0, 48000, 48000);
+        for (quality, limit) in QUALITIES.iter().zip(limits) {
+            let output = resample(&input, 1, 48000, 22050, *quality);
+            assert_eq!(output.len(), 22050);
+            let alias_db = rms_db(&output) - rms_db(&input);
+            assert!(alias_db < limit, "{:?}: alias {} dB", quality, alias_db);
+        }
+    }
+
+    #[test]
+    fn large_downsampling_ratios_keep_their_stopband() {
+        // 5 kHz at 96 kHz would fold to 3 kHz at 8 kHz; the kernel has to grow twelvefold for
+        // the transition band to stay as narrow as at small ratios
+        let limits = [-50.0, -90.0, -105.0];
+        let input = sine(5000.0, 96000, 48000);
+        for (quality, limit) in QUALITIES.iter().zip(limits) {
+            let output = resample(&input, 1, 96000, 8000, *quality);
+            assert_eq!(output.len(), 4000);
+            let alias_db = rms_db(&output) - rms_db(&input);
+            assert!(alias_db < limit, "{:?}: alias {} dB", quality, alias_db);
+        }
+
+        // Just above the output Nyquist, outside the transition band of the longer presets
+        let input = sine(4500.0, 48000, 48000);
+        for (quality, limit) in QUALITIES[1..].iter().zip([-80.0, -100.0]) {
+            let output = resample(&input, 1, 48000, 8000, *quality);
+            let alias_db = rms_db(&output) - rms_db(&input);
+            assert!(alias_db < limit, "{:?}: alias {} dB", quality, alias_db);
+        }
+    }
+
+    #[test]
+    fn large_downsampling_ratios_keep_the_passband() {
+        let input = sine(1000.0, 96000, 48000);
+        let output = resample(&input, 1, 96000, 8000, ResamplerQuality::Medium);
+        let expected = sine(1000.0, 8000, 4000);
+        let error: Vec<f32> = output.iter().zip(&expected).map(|(a, b)| a - b).collect();
+        assert!(rms_db(&error) - rms_db(&expected) < -75.0);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn any_conversion_keeps_its_length_and_level(
+            input_rate in 4000_u32..200_000,
+            output_rate in 4000_u32..200_000,
+            quality in proptest::sample::select(QUALITIES.to_vec()),
+            num_channels in 1_usize..=2,
+            samples in vec(-1.0_f32..=1.0, 0..600),
+        ) {
+            let resampler = Resampler::new(input_rate, output_rate, quality);
+            let input_frames = samples.len() / num_channels;
+            let input = &samples[..input_frames * num_channels];
+            let output = resampler.process_interleaved(input, num_channels);
+            prop_assert_eq!(output.len(), resampler.output_length(input_frames) * num_channels);
+            // Sinc ringing can overshoot the input peak, but every phase sums to unity gain
+            prop_assert!(output.iter().all(|s| s.is_finite() && s.abs() <= 2.0));
+        }
+    }
+}
@@ -0,0 +1,23 @@
+/*
+Guarding against non-finite samples.
+A single NaN or infinity (from a corrupt float file or an unstable filter) spreads through every
+recursive filter and delay line it reaches and ends up in the output file. Replacing such
+samples with silence keeps the damage local; the count lets callers warn about it.
+ */
+
+/// Replaces NaN and infinite samples with 0 and returns how many were replaced.
+pub fn sanitize(samples: &mut [f32]) -> usize {
+    let mut replaced = 0;
+    for sample in samples.iter_mut() {
+        if !sample.is_finite() {
+            *sample = 0.0;
+            replaced += 1;
+        }
+    }
+    replaced
+}
+
+/// Returns true if every sample is finite.
+pub fn is_finite(samples: &[f32]) -> bool {
+    samples.iter().all(|sample| sample.is_finite())
+}
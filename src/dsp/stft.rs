@@ -0,0 +1,249 @@
+/*
+Short-time Fourier transform helpers, and the one place in the crate that talks to the FFT
+library.
+A signal is cut into overlapping frames, each frame is multiplied by a Hann window and
+transformed to a half spectrum (DC up to Nyquist). After the spectra have been modified, every
+frame is transformed back, windowed again and overlap-added. The hop size must divide the frame
+size with at least 4x overlap, which makes the squared Hann window sum to a constant (COLA), so
+an untouched spectrum reconstructs the input exactly.
+ */
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+pub use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+
+/// Returns a periodic Hann window of `size` samples.
+pub fn hann_window(size: usize) -> Vec<f32> {
+    (0..size)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / size as f32).cos())
+        .collect()
+}
+
+/// Forward and inverse FFTs of a fixed size working on real signals.
+pub struct Fft {
+    size: usize,
+    forward: Arc<dyn rustfft::Fft<f32>>,
+    inverse: Arc<dyn rustfft::Fft<f32>>,
+}
+
+impl Fft {
+    pub fn new(size: usize) -> Self {
+        let mut planner = FftPlanner::new();
+        Fft {
+            size,
+            forward: planner.plan_fft_forward(size),
+            inverse: planner.plan_fft_inverse(size),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of bins in a half spectrum, from DC to Nyquist.
+    pub fn num_bins(&self) -> usize {
+        self.size / 2 + 1
+    }
+
+    /// Transforms `input` (zero-padded or truncated to the FFT size) into its half spectrum.
+    pub fn forward(&self, input: &[f32]) -> Vec<Complex<f32>> {
+        let mut buffer: Vec<Complex<f32>> = (0..self.size)
+            .map(|i| Complex::new(input.get(i).copied().unwrap_or(0.0), 0.0))
+            .collect();
+        self.forward.process(&mut buffer);
+        buffer.truncate(self.num_bins());
+        buffer
+    }
+
+    /// Transforms a half spectrum back into `size` real samples, including the 1/N scaling.
+    pub fn inverse(&self, spectrum: &[Complex<f32>]) -> Vec<f32> {
+        let mut buffer = vec![Complex::new(0.0, 0.0); self.size];
+        for (bin, value) in spectrum.iter().take(self.num_bins()).enumerate() {
+            buffer[bin] = *value;
+            // Mirror the conjugate so the time domain result is real
+            if bin > 0 && bin < self.size - bin {
+                buffer[self.size - bin] = value.conj();
+            }
+        }
+        self.inverse.process(&mut buffer);
+
+        let scale = 1.0 / self.size as f32;
+        buffer.iter().map(|value| value.re * scale).collect()
+    }
+}
+
+/// Windowed analysis and overlap-add resynthesis with a fixed frame and hop size.
+pub struct Stft {
+    fft: Fft,
+    hop_size: usize,
+    window: Vec<f32>,
+}
+
+impl Stft {
+    /// Panics if `hop_size` does not divide `frame_size` with at least 4x overlap.
+    pub fn new(frame_size: usize, hop_size: usize) -> Self {
+        assert!(
+            hop_size > 0 && frame_size.is_multiple_of(hop_size) && frame_size / hop_size >= 4,
+            "STFT hop size must divide the frame size with at least 4x overlap"
+        );
+        Stft {
+            fft: Fft::new(frame_size),
+            hop_size,
+            window: hann_window(frame_size),
+        }
+    }
+
+    pub fn frame_size(&self) -> usize {
+        self.fft.size()
+    }
+
+    pub fn hop_size(&self) -> usize {
+        self.hop_size
+    }
+
+    pub fn num_bins(&self) -> usize {
+        self.fft.num_bins()
+    }
+
+    /// Frequency in Hz at the centre of `bin`.
+    pub fn bin_frequency(&self, bin: usize, sample_rate: f32) -> f32 {
+        bin as f32 * sample_rate / self.frame_size() as f32
+    }
+
+    /// Splits `samples` into windowed half spectra. The signal is padded at both ends so every
+    /// input sample is covered by the full overlap.
+    pub fn analyze(&self, samples: &[f32]) -> Vec<Vec<Complex<f32>>> {
+        let frame_size = self.frame_size();
+        let padding = frame_size - self.hop_size;
+        let num_frames = (samples.len() + padding).div_ceil(self.hop_size);
+
+        let mut frame = vec![0.0; frame_size];
+        (0..num_frames)
+            .map(|index| {
+                let start = (index * self.hop_size) as isize - padding as isize;
+                for (i, value) in frame.iter_mut().enumerate() {
+                    let position = start + i as isize;
+                    let sample = if position >= 0 {
+                        samples.get(position as usize).copied().unwrap_or(0.0)
+                    } else {
+                        0.0
+                    };
+                    *value = sample * self.window[i];
+                }
+                self.fft.forward(&frame)
+            })
+            .collect()
+    }
+
+    /// Overlap-adds the frames produced by `analyze` (possibly modified) into `length` samples.
+    pub fn synthesize(&self, frames: &[Vec<Complex<f32>>], length: usize) -> Vec<f32> {
+        let frame_size = self.frame_size();
+        let padding = frame_size - self.hop_size;
+        let mut output = vec![0.0; frames.len() * self.hop_size + frame_size];
+
+        for (index, spectrum) in frames.iter().enumerate() {
+            let frame = self.fft.inverse(spectrum);
+            let start = index * self.hop_size;
+            for (i, sample) in frame.iter().enumerate() {
+                output[start + i] += sample * self.window[i];
+            }
+        }
+
+        // Sum of the squared window over all overlapping frames (constant for COLA hops)
+        let overlap = frame_size / self.hop_size;
+        let norm: f32 = self.window.iter().map(|w| w * w).sum::<f32>() / frame_size as f32 * overlap as f32;
+
+        output
+            .into_iter()
+            .skip(padding)
+            .take(length)
+            .map(|sample| sample / norm)
+            .collect()
+    }
+
+    /// Runs `process_frame` on every spectrum of `samples` and resynthesizes the result.
+    pub fn process(&self, samples: &[f32], mut process_frame: impl FnMut(&mut [Complex<f32>])) -> Vec<f32> {
+        let mut frames = self.analyze(samples);
+        for frame in frames.iter_mut() {
+            process_frame(frame);
+        }
+        self.synthesize(&frames, samples.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_signal(length: usize) -> Vec<f32> {
+        (0..length)
+            .map(|n| 0.5 * (0.031 * n as f32).sin() + 0.3 * (0.47 * n as f32).cos())
+            .collect()
+    }
+
+    #[test]
+    fn hann_window_is_periodic_and_peaks_in_the_middle() {
+        let window = hann_window(8);
+        assert_eq!(window[0], 0.0);
+        assert!((window[4] - 1.0).abs() < 1e-6);
+        assert!((window[2] - window[6]).abs() < 1e-6);
+    }
+
+    #[test]
+    fn forward_puts_a_sine_in_its_bin() {
+        let fft = Fft::new(256);
+        let sine: Vec<f32> = (0..256).map(|n| (2.0 * PI * 16.0 * n as f32 / 256.0).sin()).collect();
+        let spectrum = fft.forward(&sine);
+        assert_eq!(spectrum.len(), 129);
+        assert!((spectrum[16].norm() - 128.0).abs() < 1e-2);
+        let leakage: f32 = spectrum.iter().enumerate().filter(|&(bin, _)| bin != 16).map(|(_, c)| c.norm()).sum();
+        assert!(leakage < 1e-2, "leakage {}", leakage);
+    }
+
+    #[test]
+    fn inverse_undoes_forward() {
+        let fft = Fft::new(512);
+        let signal = test_signal(512);
+        let round_trip = fft.inverse(&fft.forward(&signal));
+        for (a, b) in signal.iter().zip(round_trip.iter()) {
+            assert!((a - b).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn untouched_spectra_reconstruct_the_input() {
+        for (frame_size, hop_size) in [(1024, 256), (2048, 256), (512, 128)] {
+            let stft = Stft::new(frame_size, hop_size);
+            let signal = test_signal(5000);
+            let output = stft.process(&signal, |_| {});
+            assert_eq!(output.len(), signal.len());
+            let error = signal.iter().zip(output.iter()).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+            assert!(error < 1e-4, "frame {} hop {}: error {}", frame_size, hop_size, error);
+        }
+    }
+
+    #[test]
+    fn clearing_bins_removes_a_tone() {
+        let stft = Stft::new(1024, 256);
+        let sample_rate = 48_000.0;
+        let tone = |frequency: f32, n: usize| (2.0 * PI * frequency * n as f32 / sample_rate).sin();
+        let signal: Vec<f32> = (0..48_000).map(|n| 0.5 * tone(300.0, n) + 0.5 * tone(6000.0, n)).collect();
+        let output = stft.process(&signal, |frame| {
+            for (bin, value) in frame.iter_mut().enumerate() {
+                if stft.bin_frequency(bin, sample_rate) > 2000.0 {
+                    *value = Complex::new(0.0, 0.0);
+                }
+            }
+        });
+        // What remains in the middle of the file is the 300 Hz tone alone
+        let error = (10_000..38_000).map(|n| (output[n] - 0.5 * tone(300.0, n)).abs()).fold(0.0, f32::max);
+        assert!(error < 1e-3, "error {}", error);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 4x overlap")]
+    fn rejects_too_little_overlap() {
+        Stft::new(1024, 512);
+    }
+}
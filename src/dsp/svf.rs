@@ -0,0 +1,134 @@
+/*
+A topology-preserving transform (zero-delay feedback) state variable filter, following
+Andrew Simper's trapezoidal integrator formulation.
+Every sample produces the low-pass, band-pass and high-pass outputs at once. Because the
+integrator states keep their meaning when the coefficients change, the cutoff can be modulated
+at audio rate (sweeps, wahs, envelope filters) without the zipper noise of a direct form biquad.
+ */
+use std::f32::consts::PI;
+
+/// The simultaneous outputs of one filter step.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SvfOutput {
+    pub low_pass: f32,
+    pub band_pass: f32,
+    pub high_pass: f32,
+}
+
+impl SvfOutput {
+    /// Band-reject output, the input with the band-pass part removed.
+    pub fn notch(&self) -> f32 {
+        self.low_pass + self.high_pass
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StateVariableFilter {
+    sample_rate: f32,
+    k: f32,
+    a1: f32,
+    a2: f32,
+    a3: f32,
+    ic1eq: f32,
+    ic2eq: f32,
+}
+
+impl StateVariableFilter {
+    pub fn new(sample_rate: f32, cutoff: f32, q: f32) -> Self {
+        let mut filter = StateVariableFilter {
+            sample_rate,
+            k: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+            a3: 0.0,
+            ic1eq: 0.0,
+            ic2eq: 0.0,
+        };
+        filter.set_params(cutoff, q);
+        filter
+    }
+
+    /// Updates cutoff (Hz) and resonance (Q) without touching the filter state, so it is safe
+    /// to call once per sample.
+    pub fn set_params(&mut self, cutoff: f32, q: f32) {
+        let cutoff = cutoff.clamp(1.0, 0.49 * self.sample_rate);
+        let g = (PI * cutoff / self.sample_rate).tan();
+        self.k = 1.0 / q.max(1e-3);
+        self.a1 = 1.0 / (1.0 + g * (g + self.k));
+        self.a2 = g * self.a1;
+        self.a3 = g * self.a2;
+    }
+
+    pub fn process(&mut self, input: f32) -> SvfOutput {
+        let v3 = input - self.ic2eq;
+        let v1 = self.a1 * self.ic1eq + self.a2 * v3;
+        let v2 = self.ic2eq + self.a2 * self.ic1eq + self.a3 * v3;
+        self.ic1eq = 2.0 * v1 - self.ic1eq;
+        self.ic2eq = 2.0 * v2 - self.ic2eq;
+
+        SvfOutput {
+            low_pass: v2,
+            band_pass: v1,
+            high_pass: input - self.k * v1 - v2,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.ic1eq = 0.0;
+        self.ic2eq = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::test_util::{assert_close, gain_db, SAMPLE_RATE};
+
+    // Steady-state gain in dB of one output for a sine at `frequency`
+    fn output_gain_db(cutoff: f32, q: f32, frequency: f32, output: impl Fn(&SvfOutput) -> f32) -> f32 {
+        let mut filter = StateVariableFilter::new(SAMPLE_RATE, cutoff, q);
+        gain_db(frequency, |x| output(&filter.process(x)))
+    }
+
+    const BUTTERWORTH_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+    #[test]
+    fn low_and_high_pass_are_3_db_down_at_the_cutoff() {
+        assert_close(output_gain_db(1000.0, BUTTERWORTH_Q, 1000.0, |o| o.low_pass), -3.01, 0.05);
+        assert_close(output_gain_db(1000.0, BUTTERWORTH_Q, 1000.0, |o| o.high_pass), -3.01, 0.05);
+        assert_close(output_gain_db(1000.0, BUTTERWORTH_Q, 50.0, |o| o.low_pass), 0.0, 0.05);
+        assert_close(output_gain_db(1000.0, BUTTERWORTH_Q, 15_000.0, |o| o.high_pass), 0.0, 0.1);
+    }
+
+    #[test]
+    fn band_pass_peaks_at_q_and_notch_rejects_the_cutoff() {
+        let q: f32 = 4.0;
+        assert_close(output_gain_db(1000.0, q, 1000.0, |o| o.band_pass), 20.0 * q.log10(), 0.05);
+        assert!(output_gain_db(1000.0, q, 1000.0, SvfOutput::notch) < -40.0);
+        assert_close(output_gain_db(1000.0, q, 100.0, SvfOutput::notch), 0.0, 0.05);
+    }
+
+    #[test]
+    fn outputs_sum_back_to_the_input() {
+        let q = 2.0;
+        let mut filter = StateVariableFilter::new(SAMPLE_RATE, 800.0, q);
+        for n in 0..1000 {
+            let x = ((n * 7919) % 200) as f32 / 100.0 - 1.0;
+            let o = filter.process(x);
+            assert_close(o.low_pass + o.band_pass / q + o.high_pass, x, 1e-5);
+        }
+    }
+
+    #[test]
+    fn cutoff_can_be_swept_every_sample() {
+        let mut filter = StateVariableFilter::new(SAMPLE_RATE, 200.0, 10.0);
+        let mut peak = 0.0_f32;
+        for n in 0..SAMPLE_RATE as usize {
+            let cutoff = 200.0 + 10_000.0 * (0.5 + 0.5 * (2.0 * PI * 5.0 * n as f32 / SAMPLE_RATE).sin());
+            filter.set_params(cutoff, 10.0);
+            let x = if n % 100 < 50 { 0.5 } else { -0.5 };
+            peak = peak.max(filter.process(x).low_pass.abs());
+        }
+        assert!(peak.is_finite() && peak < 20.0, "peak {}", peak);
+    }
+}
@@ -0,0 +1,166 @@
+/*
+True-peak detection estimates the peaks of the continuous waveform a DAC reconstructs, which
+can lie between samples and exceed the largest sample value (inter-sample peaks).
+Following the approach of ITU-R BS.1770, the signal is upsampled 4x with a polyphase
+windowed-sinc interpolator (12 taps per phase) and the largest absolute value of the
+interpolated samples is taken as the true peak.
+ */
+use std::f32::consts::PI;
+
+const OVERSAMPLING: usize = 4;
+const TAPS_PER_PHASE: usize = 12;
+const CENTER: f32 = (TAPS_PER_PHASE / 2) as f32;
+
+#[derive(Debug, Clone)]
+pub struct TruePeakDetector {
+    phases: [[f32; TAPS_PER_PHASE]; OVERSAMPLING],
+    history: [f32; TAPS_PER_PHASE],
+    position: usize,
+    peak: f32,
+}
+
+impl Default for TruePeakDetector {
+    fn default() -> Self {
+        TruePeakDetector::new()
+    }
+}
+
+impl TruePeakDetector {
+    pub fn new() -> Self {
+        let mut phases = [[0.0; TAPS_PER_PHASE]; OVERSAMPLING];
+        for (phase, taps) in phases.iter_mut().enumerate() {
+            let offset = phase as f32 / OVERSAMPLING as f32;
+            for (j, tap) in taps.iter_mut().enumerate() {
+                // Distance between the interpolated position and input sample j
+                let distance = j as f32 - CENTER + offset;
+                let sinc = if distance.abs() < 1e-6 {
+                    1.0
+                } else {
+                    (PI * distance).sin() / (PI * distance)
+                };
+                let window = 0.5 + 0.5 * (PI * distance / (CENTER + 1.0)).cos();
+                *tap = sinc * window;
+            }
+            // Unity gain at DC for every phase
+            let sum: f32 = taps.iter().sum();
+            for tap in taps.iter_mut() {
+                *tap /= sum;
+            }
+        }
+
+        TruePeakDetector {
+            phases,
+            history: [0.0; TAPS_PER_PHASE],
+            position: 0,
+            peak: 0.0,
+        }
+    }
+
+    /// Feeds one sample and returns the largest absolute value among its 4 interpolated
+    /// samples. The estimate lags the input by half the filter length (6 samples).
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.history[self.position] = input;
+        self.position = (self.position + 1) % TAPS_PER_PHASE;
+
+        let mut block_peak: f32 = 0.0;
+        for taps in &self.phases {
+            // taps[j] weights the sample written j samples before the newest one
+            let value: f32 = taps
+                .iter()
+                .enumerate()
+                .map(|(j, tap)| tap * self.history[(self.position + TAPS_PER_PHASE - 1 - j) % TAPS_PER_PHASE])
+                .sum();
+            block_peak = block_peak.max(value.abs());
+        }
+
+        self.peak = self.peak.max(block_peak);
+        block_peak
+    }
+
+    /// The highest true peak seen since creation or the last reset, as a linear amplitude.
+    pub fn peak(&self) -> f32 {
+        self.peak
+    }
+
+    /// The highest true peak in dBTP.
+    pub fn peak_db(&self) -> f32 {
+        20.0 * self.peak.max(1e-10).log10()
+    }
+
+    pub fn reset(&mut self) {
+        self.history = [0.0; TAPS_PER_PHASE];
+        self.peak = 0.0;
+    }
+}
+
+/// Returns the true peak of a whole buffer as a linear amplitude.
+pub fn true_peak(samples: &[f32]) -> f32 {
+    let mut detector = TruePeakDetector::new();
+    // Flush the filter so peaks near the end are not missed
+    for &sample in samples.iter().chain(std::iter::repeat_n(&0.0, TAPS_PER_PHASE)) {
+        detector.process(sample);
+    }
+    detector.peak()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::test_util::{assert_close};
+
+    #[test]
+    fn finds_the_inter_sample_peak_of_a_quarter_rate_sine() {
+        // Sampled at 45 degrees, a full-scale fs/4 sine never has a sample above 0.707
+        let samples: Vec<f32> = (0..4800).map(|n| (PI / 2.0 * n as f32 + PI / 4.0).sin()).collect();
+        let sample_peak = samples.iter().fold(0.0_f32, |max, s| max.max(s.abs()));
+        assert_close(sample_peak, std::f32::consts::FRAC_1_SQRT_2, 1e-3);
+        // The 12-tap interpolator overshoots the real peak of 1.0 slightly
+        assert_close(true_peak(&samples), 1.016, 0.002);
+    }
+
+    #[test]
+    fn settled_dc_passes_at_unity_gain() {
+        for level in [0.5, 1.0, -1.0] {
+            let mut detector = TruePeakDetector::new();
+            let settled: Vec<f32> = (0..100).map(|_| detector.process(level)).skip(TAPS_PER_PHASE).collect();
+            for value in settled {
+                assert_close(value, level.abs(), 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn a_full_scale_step_overshoots_between_samples() {
+        // The jump from silence rings like any band-limited step (Gibbs), so it reads above 0 dBTP
+        let mut samples = vec![0.0; 50];
+        samples.extend(vec![1.0; 50]);
+        assert!(true_peak(&samples) > 1.05);
+    }
+
+    #[test]
+    fn a_full_scale_low_frequency_sine_reads_0_dbtp() {
+        let samples: Vec<f32> = (0..48_000).map(|n| (2.0 * PI * 997.0 * n as f32 / 48_000.0).sin()).collect();
+        let mut detector = TruePeakDetector::new();
+        for &sample in &samples {
+            detector.process(sample);
+        }
+        assert_close(detector.peak(), 1.0, 2e-3);
+        assert_close(detector.peak_db(), 0.0, 0.02);
+    }
+
+    #[test]
+    fn silence_and_reset_read_zero() {
+        assert_eq!(true_peak(&[0.0; 100]), 0.0);
+        let mut detector = TruePeakDetector::new();
+        detector.process(1.0);
+        detector.reset();
+        assert_eq!(detector.peak(), 0.0);
+    }
+
+    #[test]
+    fn a_peak_at_the_end_of_the_buffer_is_not_missed() {
+        let mut samples = vec![0.0; 100];
+        samples[99] = 0.9;
+        assert!(true_peak(&samples) >= 0.9);
+    }
+}
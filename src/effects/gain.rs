@@ -0,0 +1,184 @@
+/*
+A utility gain stage: fixed gain in dB or as a linear factor, polarity inversion, DC offset
+removal and normalization (peak, RMS or integrated loudness).
+DC removal subtracts each channel's mean over the whole file, which is exact for a constant offset
+and leaves the rest of the spectrum untouched. Normalization measures the (DC-free) signal's
+peak, RMS or LUFS level and replaces the fixed gain with whatever brings that level to the target.
+ */
+use super::AudioEffect;
+use crate::audio_io::AudioData;
+use crate::dsp::envelope::{db_to_linear, linear_to_db};
+use crate::dsp::loudness::measure_loudness;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalize {
+    /// Scale so the highest sample peak reaches this level in dBFS.
+    Peak(f32),
+    /// Scale so the RMS level over all channels reaches this level in dBFS.
+    Rms(f32),
+    /// Scale so the integrated loudness (EBU R128) reaches this level in LUFS.
+    Lufs(f32),
+}
+
+#[derive(Debug, Clone)]
+pub struct GainEffect {
+    gain: f32,
+    invert: bool,
+    remove_dc: bool,
+    normalize: Option<Normalize>,
+    applied_gain_db: f32,
+}
+
+impl Default for GainEffect {
+    fn default() -> Self {
+        GainEffect::new()
+    }
+}
+
+impl GainEffect {
+    pub fn new() -> Self {
+        GainEffect {
+            gain: 1.0,
+            invert: false,
+            remove_dc: false,
+            normalize: None,
+            applied_gain_db: 0.0,
+        }
+    }
+
+    pub fn set_gain_db(&mut self, gain_db: f32) {
+        self.gain = db_to_linear(gain_db);
+    }
+
+    /// Sets the gain as a linear factor; negative factors are taken as their magnitude.
+    pub fn set_gain_linear(&mut self, gain: f32) {
+        self.gain = gain.abs();
+    }
+
+    pub fn set_invert(&mut self, invert: bool) {
+        self.invert = invert;
+    }
+
+    pub fn set_remove_dc(&mut self, remove_dc: bool) {
+        self.remove_dc = remove_dc;
+    }
+
+    /// Normalizes to a target level instead of applying the fixed gain.
+    pub fn set_normalize(&mut self, normalize: Option<Normalize>) {
+        self.normalize = normalize;
+    }
+
+    /// The gain in dB applied by the last call to `process` (useful with normalization).
+    pub fn applied_gain_db(&self) -> f32 {
+        self.applied_gain_db
+    }
+}
+
+impl AudioEffect for GainEffect {
+    fn name(&self) -> &str {
+        "gain"
+    }
+
+    fn process(&mut self, input: &AudioData) -> AudioData {
+        let num_channels = input.num_channels;
+        let mut samples = input.samples.clone();
+
+        if self.remove_dc {
+            let num_frames = input.num_frames().max(1) as f64;
+            for channel in 0..num_channels {
+                let mean = samples.iter().skip(channel).step_by(num_channels).map(|&s| s as f64).sum::<f64>() / num_frames;
+                samples.iter_mut().skip(channel).step_by(num_channels).for_each(|s| *s -= mean as f32);
+            }
+        }
+
+        let gain = match self.normalize {
+            None => self.gain,
+            Some(normalize) => {
+                // Current level and target, both in dB
+                let (level_db, target_db) = match normalize {
+                    Normalize::Peak(target_db) => {
+                        let peak = samples.iter().fold(0.0_f32, |peak, s| peak.max(s.abs()));
+                        (20.0 * peak.log10(), target_db)
+                    }
+                    Normalize::Rms(target_db) => {
+                        let sum: f64 = samples.iter().map(|&s| (s as f64).powi(2)).sum();
+                        (10.0 * (sum / samples.len().max(1) as f64).log10() as f32, target_db)
+                    }
+                    Normalize::Lufs(target_db) => {
+                        let loudness = measure_loudness(&samples, num_channels, input.sample_rate);
+                        (loudness.integrated_lufs, target_db)
+                    }
+                };
+                // Silence stays silent rather than being blown up to the target
+                if level_db.is_finite() {
+                    db_to_linear(target_db - level_db)
+                } else {
+                    1.0
+                }
+            }
+        };
+        self.applied_gain_db = linear_to_db(gain);
+
+        let gain = if self.invert { -gain } else { gain };
+        samples.iter_mut().for_each(|s| *s *= gain);
+
+        AudioData::new(samples, input.sample_rate, num_channels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::test_util::assert_close;
+
+    fn stereo(samples: &[f32]) -> AudioData {
+        AudioData::new(samples.to_vec(), 48000, 2)
+    }
+
+    #[test]
+    fn default_gain_is_transparent() {
+        let input = stereo(&[0.5, -0.25, 0.1, 0.0]);
+        assert_eq!(GainEffect::new().process(&input), input);
+    }
+
+    #[test]
+    fn fixed_gain_and_inversion() {
+        let mut gain = GainEffect::new();
+        gain.set_gain_db(-6.0206);
+        gain.set_invert(true);
+        let output = gain.process(&stereo(&[0.5, -0.25]));
+        assert_close(output.samples[0], -0.25, 1e-4);
+        assert_close(output.samples[1], 0.125, 1e-4);
+        assert_close(gain.applied_gain_db(), -6.0206, 1e-4);
+    }
+
+    #[test]
+    fn dc_is_removed_per_channel() {
+        let mut gain = GainEffect::new();
+        gain.set_remove_dc(true);
+        let output = gain.process(&stereo(&[0.6, -0.1, 0.4, -0.3]));
+        assert_eq!(output.samples.len(), 4);
+        for (actual, expected) in output.samples.iter().zip([0.1, 0.1, -0.1, -0.1]) {
+            assert_close(*actual, expected, 1e-6);
+        }
+    }
+
+    #[test]
+    fn peak_normalization_reaches_the_target() {
+        let mut gain = GainEffect::new();
+        gain.set_normalize(Some(Normalize::Peak(-6.0206)));
+        let output = gain.process(&stereo(&[0.1, -0.2, 0.05, 0.0]));
+        let peak = output.samples.iter().fold(0.0_f32, |peak, s| peak.max(s.abs()));
+        assert_close(peak, 0.5, 1e-4);
+        assert_close(gain.applied_gain_db(), 7.9588, 1e-3);
+    }
+
+    #[test]
+    fn silence_is_not_normalized() {
+        let mut gain = GainEffect::new();
+        gain.set_normalize(Some(Normalize::Rms(-20.0)));
+        let output = gain.process(&stereo(&[0.0; 8]));
+        assert!(output.samples.iter().all(|&s| s == 0.0));
+        assert_eq!(gain.applied_gain_db(), 0.0);
+    }
+}
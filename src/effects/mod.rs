@@ -0,0 +1,281 @@
+/*
+Effects process a whole `AudioData` buffer and return the result. The output keeps the input's
+sample rate and channel count, but may be longer than the input (a reverb tail, for example).
+`ResampleEffect` is the one exception to the sample rate rule, and `TimeStretchEffect` the one
+effect whose output can also be shorter.
+Effects that keep ringing after their input stops (reverbs, delays) report how long through
+`tail_secs`; callers that want the whole decay append that much silence to the input.
+Effects that delay their output (lookahead, block-based FFT processing) report the delay through
+`latency_samples`, so callers can drop that many frames from the start and stay time-aligned.
+ */
+use crate::audio_io::AudioData;
+
+pub mod gain;
+pub mod granular;
+pub mod pan;
+pub mod parallel_mix;
+pub mod pitch_shift;
+pub mod resample;
+pub mod reverb;
+pub mod stereo_width;
+pub mod tape;
+pub mod time_stretch;
+pub mod vocoder;
+
+pub use gain::GainEffect;
+pub use granular::GranularEffect;
+pub use pan::PanEffect;
+pub use parallel_mix::ParallelMix;
+pub use pitch_shift::PitchShiftEffect;
+pub use resample::ResampleEffect;
+pub use reverb::{ReverbEffect, ReverbModel};
+pub use stereo_width::StereoWidthEffect;
+pub use tape::TapeEffect;
+pub use time_stretch::TimeStretchEffect;
+pub use vocoder::VocoderEffect;
+
+pub trait AudioEffect {
+    /// Short name used in messages, e.g. "reverb".
+    fn name(&self) -> &str;
+
+    /// Processes an interleaved buffer and returns the processed audio.
+    fn process(&mut self, input: &AudioData) -> AudioData;
+
+    /// The longest the output can keep sounding after the input ends, in seconds.
+    fn tail_secs(&self) -> f32 {
+        0.0
+    }
+
+    /// How many frames the output lags behind the input.
+    fn latency_samples(&self) -> usize {
+        0
+    }
+}
+
+// Lets wrappers such as `ParallelMix` take borrowed or type-erased effects
+impl<E: AudioEffect + ?Sized> AudioEffect for &mut E {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn process(&mut self, input: &AudioData) -> AudioData {
+        (**self).process(input)
+    }
+
+    fn tail_secs(&self) -> f32 {
+        (**self).tail_secs()
+    }
+
+    fn latency_samples(&self) -> usize {
+        (**self).latency_samples()
+    }
+}
+
+// Random settings, in and out of range, and random input for every effect: whatever they are
+// given, effects must not panic and must return finite audio at a sane level
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::{ReflectionPattern, Waveform};
+    use gain::Normalize;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use proptest::sample::select;
+    use vocoder::Carrier;
+
+    // Highest peak any effect may produce from full-scale input (+24 dB)
+    const LIMIT: f32 = 16.0;
+
+    fn input() -> impl Strategy<Value = AudioData> {
+        (1_usize..=2, select(vec![8000_u32, 44100, 48000, 96000]), 0_usize..2048).prop_flat_map(
+            |(num_channels, sample_rate, num_frames)| {
+                vec(-1.0_f32..=1.0, num_frames * num_channels)
+                    .prop_map(move |samples| AudioData::new(samples, sample_rate, num_channels))
+            },
+        )
+    }
+
+    fn check(input: &AudioData, output: &AudioData) -> Result<(), TestCaseError> {
+        prop_assert_eq!(output.num_channels, input.num_channels);
+        prop_assert_eq!(output.samples.len() % output.num_channels, 0);
+        for &sample in &output.samples {
+            prop_assert!(sample.is_finite() && sample.abs() <= LIMIT, "sample {}", sample);
+        }
+        Ok(())
+    }
+
+    fn check_effect(mut effect: impl AudioEffect, input: &AudioData) -> Result<(), TestCaseError> {
+        let output = effect.process(input);
+        prop_assert_eq!(output.sample_rate, input.sample_rate);
+        prop_assert!(effect.tail_secs().is_finite() && effect.tail_secs() >= 0.0);
+        check(input, &output)
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn gain(
+            input in input(),
+            gain_db in -60.0_f32..24.0,
+            invert: bool,
+            remove_dc: bool,
+            normalize in prop_oneof![
+                Just(None),
+                (-60.0_f32..0.0).prop_map(|db| Some(Normalize::Peak(db))),
+                (-60.0_f32..-12.0).prop_map(|db| Some(Normalize::Rms(db))),
+                (-60.0_f32..-12.0).prop_map(|db| Some(Normalize::Lufs(db))),
+            ],
+        ) {
+            let mut gain = GainEffect::new();
+            gain.set_gain_db(gain_db);
+            gain.set_invert(invert);
+            gain.set_remove_dc(remove_dc);
+            gain.set_normalize(normalize);
+            check_effect(gain, &input)?;
+        }
+
+        #[test]
+        fn pan(
+            input in input(),
+            position in -3.0_f32..3.0,
+            auto_pan in proptest::option::of(-10.0_f32..100.0),
+            depth in -1.0_f32..2.0,
+            waveform in select(vec![Waveform::Sine, Waveform::Triangle, Waveform::Saw, Waveform::Square, Waveform::SampleAndHold]),
+        ) {
+            let mut pan = PanEffect::new();
+            pan.set_position(position);
+            pan.set_auto_pan(auto_pan);
+            pan.set_depth(depth);
+            pan.set_waveform(waveform);
+            check_effect(pan, &input)?;
+        }
+
+        #[test]
+        fn stereo_width(
+            input in input(),
+            width in -1.0_f32..3.0,
+            mid_gain_db in -60.0_f32..6.0,
+            side_gain_db in -60.0_f32..6.0,
+            bass_mono in proptest::option::of(-100.0_f32..100_000.0),
+        ) {
+            let mut stereo_width = StereoWidthEffect::new();
+            stereo_width.set_width(width);
+            stereo_width.set_mid_gain_db(mid_gain_db);
+            stereo_width.set_side_gain_db(side_gain_db);
+            stereo_width.set_bass_mono(bass_mono);
+            check_effect(stereo_width, &input)?;
+        }
+
+        #[test]
+        fn reverb(
+            input in input(),
+            model in select(vec![ReverbModel::Classic, ReverbModel::Freeverb]),
+            wet_only: bool,
+            sizes in (-1.0_f32..2.0, -1.0_f32..2.0, -1.0_f32..2.0, -1.0_f32..3.0, -1.0_f32..60.0),
+            er_pattern in select(vec![ReflectionPattern::Room, ReflectionPattern::Hall, ReflectionPattern::Plate]),
+            er in (-1.0_f32..2.0, -1.0_f32..4.0),
+            gate in (proptest::option::of(-120.0_f32..10.0), -100.0_f32..10_000.0),
+            ducking in (proptest::option::of(-120.0_f32..10.0), -100.0_f32..100.0, -100.0_f32..10_000.0),
+        ) {
+            let mut reverb = ReverbEffect::new();
+            reverb.set_model(model);
+            reverb.set_wet_only(wet_only);
+            let (room_size, damping, modulation, width, hf_decay) = sizes;
+            reverb.set_room_size(room_size);
+            reverb.set_damping(damping);
+            reverb.set_modulation(modulation);
+            reverb.set_width(width);
+            reverb.set_hf_decay_secs(hf_decay);
+            reverb.set_er_pattern(er_pattern);
+            reverb.set_er_level(er.0);
+            reverb.set_er_size(er.1);
+            reverb.set_gate(gate.0);
+            reverb.set_gate_hold_ms(gate.1);
+            reverb.set_ducking(ducking.0);
+            reverb.set_duck_depth_db(ducking.1);
+            reverb.set_duck_release_ms(ducking.2);
+            check_effect(reverb, &input)?;
+        }
+
+        #[test]
+        fn tape(
+            input in input(),
+            drive_db in -10.0_f32..40.0,
+            rolloff_hz in -1000.0_f32..100_000.0,
+            wow in -1.0_f32..2.0,
+            flutter in -1.0_f32..2.0,
+        ) {
+            let mut tape = TapeEffect::new();
+            tape.set_drive_db(drive_db);
+            tape.set_rolloff_hz(rolloff_hz);
+            tape.set_wow(wow);
+            tape.set_flutter(flutter);
+            check_effect(tape, &input)?;
+        }
+
+        #[test]
+        fn granular(
+            input in input(),
+            timing in (-10.0_f32..2000.0, -1.0_f32..1000.0, -100.0_f32..20_000.0),
+            pitch in (-48.0_f32..48.0, -10.0_f32..48.0),
+            reverse_probability in -1.0_f32..2.0,
+            mix in -1.0_f32..2.0,
+            seed: u32,
+        ) {
+            let mut granular = GranularEffect::new();
+            granular.set_grain_size_ms(timing.0);
+            granular.set_density(timing.1);
+            granular.set_position_spread_ms(timing.2);
+            granular.set_pitch(pitch.0);
+            granular.set_pitch_spread(pitch.1);
+            granular.set_reverse_probability(reverse_probability);
+            granular.set_mix(mix);
+            granular.set_seed(seed);
+            check_effect(granular, &input)?;
+        }
+
+        #[test]
+        fn vocoder(
+            input in input(),
+            carrier in prop_oneof![(-100.0_f32..50_000.0).prop_map(Carrier::Saw), Just(Carrier::Noise)],
+            num_bands in 0_usize..64,
+        ) {
+            let mut vocoder = VocoderEffect::new(carrier);
+            vocoder.set_num_bands(num_bands);
+            check_effect(vocoder, &input)?;
+        }
+
+        #[test]
+        fn pitch_shift(input in input(), pitch in -1.0_f32..8.0) {
+            let mut pitch_shift = PitchShiftEffect::new();
+            pitch_shift.set_pitch(pitch);
+            let output = pitch_shift.process(&input);
+            prop_assert_eq!(output.samples.len(), input.samples.len());
+            check(&input, &output)?;
+        }
+
+        #[test]
+        fn time_stretch(input in input(), stretch in -1.0_f32..8.0) {
+            let mut time_stretch = TimeStretchEffect::new();
+            time_stretch.set_stretch(stretch);
+            check_effect(time_stretch, &input)?;
+        }
+
+        #[test]
+        fn resample(input in input(), target_rate in 0_u32..200_000) {
+            let output = ResampleEffect::new(target_rate).process(&input);
+            prop_assert_eq!(output.sample_rate, target_rate.max(1));
+            check(&input, &output)?;
+        }
+
+        #[test]
+        fn parallel_mix(input in input(), mix in -1.0_f32..2.0, dry_delay in proptest::option::of(0_usize..5000)) {
+            let mut parallel = ParallelMix::new(TapeEffect::new(), mix);
+            if let Some(frames) = dry_delay {
+                parallel.set_dry_delay(frames);
+            }
+            check_effect(parallel, &input)?;
+        }
+    }
+}
@@ -0,0 +1,148 @@
+/*
+Constant-power panning of a stereo signal, static or driven by an LFO (auto-pan).
+The position runs from -1 (hard left) to 1 (hard right) and is mapped to a quarter circle, so the
+left and right gains are the cosine and sine of the same angle and move inversely. The gains are
+scaled by sqrt(2) so the centre is unity: a signal that is the same in both channels keeps its
+power at every position, and a hard pan raises the remaining side by 3 dB. In auto-pan mode the
+LFO swings the position around the static one by the depth.
+Only stereo input can be panned; other channel counts are returned unchanged.
+ */
+use std::f32::consts::{FRAC_PI_4, SQRT_2};
+
+use super::AudioEffect;
+use crate::audio_io::AudioData;
+use crate::dsp::{Lfo, Waveform};
+
+#[derive(Debug, Clone)]
+pub struct PanEffect {
+    position: f32,
+    auto_pan_rate: Option<f32>,
+    depth: f32,
+    waveform: Waveform,
+}
+
+impl Default for PanEffect {
+    fn default() -> Self {
+        PanEffect::new()
+    }
+}
+
+impl PanEffect {
+    pub fn new() -> Self {
+        PanEffect {
+            position: 0.0,
+            auto_pan_rate: None,
+            depth: 1.0,
+            waveform: Waveform::Sine,
+        }
+    }
+
+    /// Static position from -1 (left) to 1 (right), and the centre of the auto-pan sweep.
+    pub fn set_position(&mut self, position: f32) {
+        self.position = position.clamp(-1.0, 1.0);
+    }
+
+    /// Sweeps the position at `rate` Hz, or holds it still with `None`.
+    pub fn set_auto_pan(&mut self, rate: Option<f32>) {
+        self.auto_pan_rate = rate.map(|rate| rate.max(0.0));
+    }
+
+    /// How far the auto-pan swings from the static position, from 0 to 1.
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+    }
+}
+
+// Left and right gains for a position in [-1, 1]
+fn pan_gains(position: f32) -> (f32, f32) {
+    let angle = (position + 1.0) * FRAC_PI_4;
+    (angle.cos() * SQRT_2, angle.sin() * SQRT_2)
+}
+
+impl AudioEffect for PanEffect {
+    fn name(&self) -> &str {
+        "pan"
+    }
+
+    fn process(&mut self, input: &AudioData) -> AudioData {
+        // The centre gains are only unity up to rounding, so a centred static pan is skipped
+        if input.num_channels != 2 || (self.auto_pan_rate.is_none() && self.position == 0.0) {
+            return input.clone();
+        }
+
+        let mut lfo = self
+            .auto_pan_rate
+            .map(|rate| Lfo::new(input.sample_rate as f32, rate, self.waveform));
+
+        let mut samples = Vec::with_capacity(input.samples.len());
+        for frame in input.samples.chunks_exact(2) {
+            let position = match lfo.as_mut() {
+                Some(lfo) => (self.position + self.depth * lfo.next_value()).clamp(-1.0, 1.0),
+                None => self.position,
+            };
+            let (left_gain, right_gain) = pan_gains(position);
+            samples.push(frame[0] * left_gain);
+            samples.push(frame[1] * right_gain);
+        }
+
+        AudioData::new(samples, input.sample_rate, 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::test_util::assert_close;
+
+    fn centred(frames: usize) -> AudioData {
+        AudioData::new(vec![0.5; 2 * frames], 48000, 2)
+    }
+
+    #[test]
+    fn centre_is_transparent() {
+        let input = AudioData::new(vec![0.5, -0.25, 0.1, 0.3], 48000, 2);
+        assert_eq!(PanEffect::new().process(&input), input);
+    }
+
+    #[test]
+    fn panning_keeps_constant_power() {
+        for position in [-1.0, -0.5, 0.3, 1.0] {
+            let mut pan = PanEffect::new();
+            pan.set_position(position);
+            let output = pan.process(&centred(1));
+            let power = output.samples[0].powi(2) + output.samples[1].powi(2);
+            assert_close(power, 0.5, 1e-5);
+        }
+
+        let mut pan = PanEffect::new();
+        pan.set_position(-1.0);
+        let output = pan.process(&centred(1));
+        assert_close(output.samples[0], 0.5 * SQRT_2, 1e-6);
+        assert_close(output.samples[1], 0.0, 1e-6);
+    }
+
+    #[test]
+    fn auto_pan_stays_within_the_depth() {
+        let mut pan = PanEffect::new();
+        pan.set_auto_pan(Some(5.0));
+        pan.set_depth(0.5);
+        let output = pan.process(&centred(48000));
+        let (limit_left, limit_right) = pan_gains(0.5);
+        for frame in output.samples.chunks_exact(2) {
+            assert!(frame[0] <= 0.5 * limit_right + 1e-5 && frame[1] <= 0.5 * limit_right + 1e-5);
+            assert!(frame[0] >= 0.5 * limit_left - 1e-5 && frame[1] >= 0.5 * limit_left - 1e-5);
+        }
+    }
+
+    #[test]
+    fn mono_is_returned_unchanged() {
+        let input = AudioData::new(vec![0.5, -0.25], 48000, 1);
+        let mut pan = PanEffect::new();
+        pan.set_position(1.0);
+        assert_eq!(pan.process(&input), input);
+    }
+}
@@ -0,0 +1,151 @@
+/*
+Parallel ("New York") processing for any effect: the processed signal is blended with a dry copy
+of the input, so effects without their own mix control (compressors, saturation) can be used in
+parallel. The dry copy is delayed by the effect's reported latency (or a delay set by hand) so
+both line up; without that the blend would comb filter. When the effect's output is longer than
+the input (a reverb tail), the dry signal is treated as silence past its end.
+ */
+use super::AudioEffect;
+use crate::audio_io::AudioData;
+
+#[derive(Debug, Clone)]
+pub struct ParallelMix<E> {
+    effect: E,
+    mix: f32,
+    dry_delay: Option<usize>,
+}
+
+impl<E: AudioEffect> ParallelMix<E> {
+    /// Wraps `effect`; `mix` is the wet proportion from 0 (dry only) to 1 (wet only).
+    pub fn new(effect: E, mix: f32) -> Self {
+        ParallelMix {
+            effect,
+            mix: mix.clamp(0.0, 1.0),
+            dry_delay: None,
+        }
+    }
+
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    /// Delays the dry signal by this many frames instead of the effect's reported latency.
+    pub fn set_dry_delay(&mut self, frames: usize) {
+        self.dry_delay = Some(frames);
+    }
+
+    /// The delay applied to the dry signal in frames.
+    pub fn dry_delay(&self) -> usize {
+        self.dry_delay.unwrap_or_else(|| self.effect.latency_samples())
+    }
+
+    pub fn effect(&self) -> &E {
+        &self.effect
+    }
+
+    pub fn effect_mut(&mut self) -> &mut E {
+        &mut self.effect
+    }
+
+    pub fn into_inner(self) -> E {
+        self.effect
+    }
+}
+
+impl<E: AudioEffect> AudioEffect for ParallelMix<E> {
+    fn name(&self) -> &str {
+        self.effect.name()
+    }
+
+    fn tail_secs(&self) -> f32 {
+        self.effect.tail_secs()
+    }
+
+    // The dry signal is delayed to match the wet one, so the blend lags by the dry delay
+    fn latency_samples(&self) -> usize {
+        self.dry_delay()
+    }
+
+    fn process(&mut self, input: &AudioData) -> AudioData {
+        let wet = self.effect.process(input);
+        let offset = self.dry_delay() * input.num_channels;
+        let length = wet.samples.len().max(input.samples.len() + offset);
+
+        let samples = (0..length)
+            .map(|i| {
+                let dry = i
+                    .checked_sub(offset)
+                    .and_then(|j| input.samples.get(j))
+                    .copied()
+                    .unwrap_or(0.0);
+                let wet = wet.samples.get(i).copied().unwrap_or(0.0);
+                dry * (1.0 - self.mix) + wet * self.mix
+            })
+            .collect();
+
+        AudioData::new(samples, wet.sample_rate, wet.num_channels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::test_util::assert_close;
+    use crate::effects::GainEffect;
+
+    // Delays its input by `latency` frames and doubles it
+    struct Late {
+        latency: usize,
+    }
+
+    impl AudioEffect for Late {
+        fn name(&self) -> &str {
+            "late"
+        }
+
+        fn process(&mut self, input: &AudioData) -> AudioData {
+            let mut samples = vec![0.0; self.latency * input.num_channels];
+            samples.extend(input.samples.iter().map(|s| 2.0 * s));
+            AudioData::new(samples, input.sample_rate, input.num_channels)
+        }
+
+        fn latency_samples(&self) -> usize {
+            self.latency
+        }
+    }
+
+    fn ramp() -> AudioData {
+        AudioData::new((1..=20).map(|i| i as f32 / 20.0).collect(), 48000, 2)
+    }
+
+    #[test]
+    fn zero_mix_is_the_dry_signal() {
+        let input = ramp();
+        let mut gain = GainEffect::new();
+        gain.set_gain_db(12.0);
+        assert_eq!(ParallelMix::new(gain, 0.0).process(&input), input);
+    }
+
+    #[test]
+    fn dry_is_delayed_by_the_effect_latency() {
+        let input = ramp();
+        let mut mix = ParallelMix::new(Late { latency: 3 }, 0.5);
+        assert_eq!(mix.latency_samples(), 3);
+        let output = mix.process(&input);
+        assert_eq!(output.samples.len(), input.samples.len() + 2 * 3);
+        // Aligned, the blend is 1.5 times the input, delayed by the latency
+        assert!(output.samples[..6].iter().all(|&s| s == 0.0));
+        for (actual, expected) in output.samples[6..].iter().zip(&input.samples) {
+            assert_close(*actual, 1.5 * expected, 1e-6);
+        }
+    }
+
+    #[test]
+    fn manual_dry_delay_overrides_the_latency() {
+        let mut mix = ParallelMix::new(Late { latency: 3 }, 1.0);
+        mix.set_dry_delay(0);
+        assert_eq!(mix.latency_samples(), 0);
+        let output = mix.process(&ramp());
+        assert_close(output.samples[6], 2.0 / 20.0, 1e-6);
+    }
+}
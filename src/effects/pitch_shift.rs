@@ -0,0 +1,117 @@
+/*
+Pitch shifting: transposes the input without changing its duration. The input is first time
+stretched by the pitch ratio with the phase vocoder (see `time_stretch`), then resampled back to
+its original length with `dsp::resampler`, which raises or lowers every frequency by the same
+ratio. The resampler works between whole sample rates, so the ratio is rounded to the nearest
+one the input rate allows; at 44.1 kHz and above the error is far below a cent.
+A pitch of exactly 1 passes the input through untouched.
+ */
+use super::time_stretch::TimeStretchEffect;
+use super::AudioEffect;
+use crate::audio_io::AudioData;
+use crate::dsp::{Resampler, ResamplerQuality};
+
+#[derive(Debug, Clone)]
+pub struct PitchShiftEffect {
+    pitch: f32,
+    quality: ResamplerQuality,
+}
+
+impl Default for PitchShiftEffect {
+    fn default() -> Self {
+        PitchShiftEffect::new()
+    }
+}
+
+impl PitchShiftEffect {
+    pub fn new() -> Self {
+        PitchShiftEffect {
+            pitch: 1.0,
+            quality: ResamplerQuality::default(),
+        }
+    }
+
+    /// Frequency ratio, clamped to 0.25-4 (two octaves down to two octaves up).
+    pub fn set_pitch(&mut self, pitch: f32) {
+        self.pitch = pitch.clamp(0.25, 4.0);
+    }
+
+    /// Transposition in semitones, clamped to +-24.
+    pub fn set_semitones(&mut self, semitones: f32) {
+        self.set_pitch(2.0_f32.powf(semitones.clamp(-24.0, 24.0) / 12.0));
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    pub fn set_quality(&mut self, quality: ResamplerQuality) {
+        self.quality = quality;
+    }
+}
+
+impl AudioEffect for PitchShiftEffect {
+    fn name(&self) -> &str {
+        "pitch-shift"
+    }
+
+    fn process(&mut self, input: &AudioData) -> AudioData {
+        if self.pitch == 1.0 {
+            return input.clone();
+        }
+        let num_channels = input.num_channels;
+        // Playing the stretched signal as if it were at this rate transposes it by the pitch ratio
+        let source_rate = ((input.sample_rate as f64 * self.pitch as f64).round() as u32).max(1);
+
+        let mut stretch = TimeStretchEffect::new();
+        stretch.set_stretch((source_rate as f64 / input.sample_rate as f64) as f32);
+        let stretched = stretch.process(input);
+        let resampler = Resampler::new(source_rate, input.sample_rate, self.quality);
+        let mut samples = resampler.process_interleaved(&stretched.samples, num_channels);
+        samples.resize(input.samples.len(), 0.0);
+
+        AudioData::new(samples, input.sample_rate, num_channels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::goertzel_power;
+    use crate::dsp::test_util::{sine, SAMPLE_RATE};
+
+    fn tone(frequency: f32) -> AudioData {
+        let samples = (0..SAMPLE_RATE as usize).map(|n| 0.5 * sine(frequency, n)).collect();
+        AudioData::new(samples, SAMPLE_RATE as u32, 1)
+    }
+
+    #[test]
+    fn sine_is_transposed_and_keeps_its_length() {
+        for (pitch, target) in [(2.0, 880.0), (0.5, 220.0), (1.5, 660.0)] {
+            let mut shift = PitchShiftEffect::new();
+            shift.set_pitch(pitch);
+            let output = shift.process(&tone(440.0));
+            assert_eq!(output.num_frames(), SAMPLE_RATE as usize);
+            let middle = &output.samples[12_000..36_000];
+            let at_target = goertzel_power(middle, target, SAMPLE_RATE);
+            assert!(goertzel_power(middle, 440.0, SAMPLE_RATE) < at_target / 1000.0, "pitch {}", pitch);
+            let level = (middle.iter().map(|s| s * s).sum::<f32>() / middle.len() as f32).sqrt();
+            assert!((level - 0.5 / 2.0_f32.sqrt()).abs() < 0.03, "pitch {}: level {}", pitch, level);
+        }
+    }
+
+    #[test]
+    fn semitones_set_the_ratio() {
+        let mut shift = PitchShiftEffect::new();
+        shift.set_semitones(12.0);
+        assert!((shift.pitch() - 2.0).abs() < 1e-6);
+        shift.set_semitones(-36.0);
+        assert!((shift.pitch() - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn unit_pitch_is_transparent() {
+        let input = tone(440.0);
+        assert_eq!(PitchShiftEffect::new().process(&input), input);
+    }
+}
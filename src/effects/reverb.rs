@@ -0,0 +1,610 @@
+/*
+A reverb with two models.
+The classic model is a feedback delay reverb: three delay lines of different lengths are fed
+with the input and with their own low-pass filtered output, and their outputs are averaged with
+the dry signal. Every second the line lengths grow by 100 samples, which keeps the echo pattern
+from settling into a fixed comb.
+Each channel has its own lines and damping state; further channels read their lines slightly
+shorter, so the wet signal is decorrelated between channels, and the width control rescales
+the side component of a stereo wet signal. The feedback damping is set by the high-frequency
+decay time, how long the top of the spectrum takes to fall by 60 dB.
+The Freeverb model is Jezar's Schroeder-Moorer design: every channel runs eight damped feedback
+combs in parallel into four allpasses in series, all fed with the mix of the input channels.
+Each comb has its own damping filter, and each further channel's delays are slightly longer,
+which decorrelates the channels. Room size sets the comb feedback and damping the low-pass in
+the comb loops. Modulation slowly sweeps every comb's delay with its own LFO, which smears the
+resonances of the tail into a chorused wash.
+Either model can be preceded by early reflections, a tapped delay line per channel whose
+pattern (room, hall or plate) and size set the spacing of the first echoes. They are mixed into
+the wet signal at the early reflections level, ahead of the diffuse tail.
+The wet signal can also follow the dynamics of the dry one. A gated reverb cuts the tail off a
+hold time after the input falls below the gate threshold, the abrupt 80s drum sound. A ducking
+reverb pulls the tail down by up to the ducking depth while the input is above the ducking
+threshold, and lets it swell back once the input quietens, so it does not cloud the dry signal.
+Both follow the peak level of the loudest input channel, and every channel shares the same gain.
+The output is as long as the input. To hear the tail, callers pad the input with silence;
+`tail_secs` estimates how long the tail takes to fall by 60 dB from the feedback of the longest
+loop, for --tail-seconds to render.
+ */
+use super::AudioEffect;
+use crate::audio_io::AudioData;
+use crate::dsp::envelope::{db_to_linear, linear_to_db, time_constant};
+use crate::dsp::{
+    DetectionMode, EarlyReflections, EnvelopeDetector, FeedbackComb, GainComputer, GainMode, Lfo, ReflectionPattern,
+    SchroederAllpass, Waveform,
+};
+
+const SAMPLE_RATE: u32 = 44100;
+const DELAY_TIME_MS: f64 = 200.0;
+const FEEDBACK: f32 = 0.5;
+const WET_DRY_MIX: f32 = 0.5;
+const NUM_DELAY_LINES: usize = 3;
+const DEFAULT_HF_DECAY_S: f32 = 0.8; // time for the highest frequencies to decay by 60 dB
+const DENORMAL_THRESHOLD: f32 = 1e-15;
+const SILENCE_THRESHOLD: f32 = 1e-6; // -120 dBFS
+const SILENCE_BLOCK_SIZE: usize = 1024;
+const DECAY_DB: f32 = 60.0; // the tail estimate is the time to fall by this much
+const STEREO_SPREAD: usize = 23; // samples by which each further channel's delay lines are shorter
+
+// Freeverb tunings, in samples at 44.1 kHz
+const FREEVERB_COMBS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const FREEVERB_ALLPASSES: [usize; 4] = [556, 441, 341, 225];
+const FREEVERB_ALLPASS_GAIN: f32 = 0.5;
+const FREEVERB_INPUT_GAIN: f32 = 0.03;
+const FREEVERB_WET_GAIN: f32 = 3.0;
+const ROOM_SCALE: f32 = 0.28; // comb feedback is 0.7 plus this times the room size
+const ROOM_OFFSET: f32 = 0.7;
+const DAMPING_SCALE: f32 = 0.4;
+const DEFAULT_ROOM_SIZE: f32 = 0.5;
+const DEFAULT_DAMPING: f32 = 0.5;
+const MAX_MODULATION_MS: f32 = 0.5; // comb delay swing at full modulation depth
+const MODULATION_RATE_HZ: f32 = 0.5; // rate of the first comb's LFO; the others run slightly faster
+const MODULATION_RATE_STEP_HZ: f32 = 0.13;
+
+const DETECTOR_RELEASE_MS: f32 = 20.0; // keeps the gate and ducking from following single cycles
+const DEFAULT_GATE_HOLD_MS: f32 = 250.0;
+const GATE_OPEN_MS: f32 = 0.5;
+const GATE_CLOSE_MS: f32 = 15.0;
+const DEFAULT_DUCK_DEPTH_DB: f32 = 12.0;
+const DEFAULT_DUCK_RELEASE_MS: f32 = 500.0;
+const DUCK_ATTACK_MS: f32 = 10.0;
+const DUCK_RATIO: f32 = 4.0;
+
+/// The reverb algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReverbModel {
+    /// Three growing feedback delay lines.
+    #[default]
+    Classic,
+    /// Eight damped combs into four allpasses per channel.
+    Freeverb,
+}
+
+// Decaying feedback eventually reaches denormal values, which are very slow to compute on x86.
+// Anything this small is far below audibility, so it is flushed to zero instead.
+fn flush_denormal(x: f32) -> f32 {
+    if x.abs() < DENORMAL_THRESHOLD {
+        0.0
+    } else {
+        x
+    }
+}
+
+// Each delay line damps its feedback with its own one-pole low-pass. The coefficient is chosen per
+// line so that, at Nyquist, the loop gain decays by 60 dB over `hf_decay_s` whatever the line
+// length, so the high frequencies die away at the same rate in every line.
+fn damping_coefficient(delay_length: usize, hf_decay_s: f32) -> f32 {
+    let delay_s = delay_length as f32 / SAMPLE_RATE as f32;
+    let hf_gain = 10f32.powf(-3.0 * delay_s / hf_decay_s);
+    // The one-pole gain at Nyquist is (1 - d) / (1 + d); solve for d
+    let ratio = (hf_gain / FEEDBACK).min(1.0);
+    (1.0 - ratio) / (1.0 + ratio)
+}
+
+#[derive(Debug, Clone)]
+pub struct ReverbEffect {
+    model: ReverbModel,
+    wet_only: bool,
+    width: f32,
+    hf_decay_s: f32,
+    room_size: f32,
+    damping: f32,
+    modulation: f32,
+    er_pattern: ReflectionPattern,
+    er_level: f32,
+    er_size: f32,
+    gate_threshold_db: Option<f32>,
+    gate_hold_ms: f32,
+    duck_threshold_db: Option<f32>,
+    duck_depth_db: f32,
+    duck_release_ms: f32,
+}
+
+// One channel of the Freeverb tank
+#[derive(Debug, Clone)]
+struct FreeverbChannel {
+    combs: Vec<FeedbackComb>,
+    allpasses: Vec<SchroederAllpass>,
+    // Base delay of every comb, and its LFO when modulated
+    delays: Vec<f32>,
+    lfos: Vec<Lfo>,
+    modulation_depth: f32,
+}
+
+impl FreeverbChannel {
+    fn new(sample_rate: f32, spread: usize, feedback: f32, damping: f32, modulation: f32) -> Self {
+        let scale = sample_rate / SAMPLE_RATE as f32;
+        let modulation_depth = modulation * MAX_MODULATION_MS / 1000.0 * sample_rate;
+        let delays: Vec<f32> = FREEVERB_COMBS
+            .iter()
+            .map(|&delay| ((delay + spread) as f32 * scale).round())
+            .collect();
+        let combs = delays
+            .iter()
+            .map(|&delay| {
+                let mut comb = FeedbackComb::new(delay + modulation_depth.ceil(), feedback, damping);
+                comb.set_delay(delay);
+                comb
+            })
+            .collect();
+        let allpasses = FREEVERB_ALLPASSES
+            .iter()
+            .map(|&delay| SchroederAllpass::new(((delay + spread) as f32 * scale).round() as usize, FREEVERB_ALLPASS_GAIN))
+            .collect();
+        let lfos = (0..FREEVERB_COMBS.len())
+            .map(|j| {
+                let rate = MODULATION_RATE_HZ + MODULATION_RATE_STEP_HZ * j as f32;
+                let mut lfo = Lfo::new(sample_rate, rate, Waveform::Sine);
+                lfo.set_phase_offset(j as f32 / FREEVERB_COMBS.len() as f32);
+                lfo
+            })
+            .collect();
+        FreeverbChannel {
+            combs,
+            allpasses,
+            delays,
+            lfos,
+            modulation_depth,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let mut output = 0.0;
+        for (j, comb) in self.combs.iter_mut().enumerate() {
+            if self.modulation_depth > 0.0 {
+                // Swing between the base delay and the modulation depth above it
+                let sweep = 0.5 + 0.5 * self.lfos[j].next_value();
+                comb.set_delay(self.delays[j] + self.modulation_depth * sweep);
+            }
+            output += comb.process(input);
+        }
+        for allpass in self.allpasses.iter_mut() {
+            output = allpass.process(output);
+        }
+        output
+    }
+}
+
+impl Default for ReverbEffect {
+    fn default() -> Self {
+        ReverbEffect::new()
+    }
+}
+
+impl ReverbEffect {
+    pub fn new() -> Self {
+        ReverbEffect {
+            model: ReverbModel::default(),
+            wet_only: false,
+            width: 1.0,
+            hf_decay_s: DEFAULT_HF_DECAY_S,
+            room_size: DEFAULT_ROOM_SIZE,
+            damping: DEFAULT_DAMPING,
+            modulation: 0.0,
+            er_pattern: ReflectionPattern::default(),
+            er_level: 0.0,
+            er_size: 1.0,
+            gate_threshold_db: None,
+            gate_hold_ms: DEFAULT_GATE_HOLD_MS,
+            duck_threshold_db: None,
+            duck_depth_db: DEFAULT_DUCK_DEPTH_DB,
+            duck_release_ms: DEFAULT_DUCK_RELEASE_MS,
+        }
+    }
+
+    pub fn set_model(&mut self, model: ReverbModel) {
+        self.model = model;
+    }
+
+    /// When enabled, the dry signal is muted and the reverb is output at full level (for stems).
+    pub fn set_wet_only(&mut self, wet_only: bool) {
+        self.wet_only = wet_only;
+    }
+
+    /// Stereo width of the wet signal: 0 is mono, 1 unchanged, 2 extra wide.
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width.clamp(0.0, 2.0);
+    }
+
+    /// Classic model: how long the highest frequencies take to decay by 60 dB, from 10 ms to 30 s.
+    /// Shorter times give a darker tail.
+    pub fn set_hf_decay_secs(&mut self, secs: f32) {
+        self.hf_decay_s = secs.clamp(0.01, 30.0);
+    }
+
+    /// Freeverb room size from 0 to 1; larger rooms decay for longer.
+    pub fn set_room_size(&mut self, room_size: f32) {
+        self.room_size = room_size.clamp(0.0, 1.0);
+    }
+
+    /// Freeverb high-frequency damping from 0 (bright) to 1 (dark).
+    pub fn set_damping(&mut self, damping: f32) {
+        self.damping = damping.clamp(0.0, 1.0);
+    }
+
+    /// Freeverb comb delay modulation depth from 0 (off) to 1.
+    pub fn set_modulation(&mut self, modulation: f32) {
+        self.modulation = modulation.clamp(0.0, 1.0);
+    }
+
+    /// Spacing pattern of the early reflections.
+    pub fn set_er_pattern(&mut self, pattern: ReflectionPattern) {
+        self.er_pattern = pattern;
+    }
+
+    /// Level of the early reflections from 0 (off) to 1.
+    pub fn set_er_level(&mut self, level: f32) {
+        self.er_level = level.clamp(0.0, 1.0);
+    }
+
+    /// Scales the early reflection times, from 0.25 to 2.
+    pub fn set_er_size(&mut self, size: f32) {
+        self.er_size = size.clamp(0.25, 2.0);
+    }
+
+    /// Gates the wet signal: it is cut off once the input has stayed below this level (in dBFS)
+    /// for the hold time. `None` disables the gate.
+    pub fn set_gate(&mut self, threshold_db: Option<f32>) {
+        self.gate_threshold_db = threshold_db;
+    }
+
+    /// How long the gate stays open after the input falls below the threshold, up to 5 s.
+    pub fn set_gate_hold_ms(&mut self, hold_ms: f32) {
+        self.gate_hold_ms = hold_ms.clamp(0.0, 5000.0);
+    }
+
+    /// Ducks the wet signal while the input is above this level (in dBFS). `None` disables
+    /// ducking.
+    pub fn set_ducking(&mut self, threshold_db: Option<f32>) {
+        self.duck_threshold_db = threshold_db;
+    }
+
+    /// Largest reduction of the wet signal while ducking, up to 60 dB; the sign is ignored.
+    pub fn set_duck_depth_db(&mut self, depth_db: f32) {
+        self.duck_depth_db = depth_db.abs().min(60.0);
+    }
+
+    /// How long the wet signal takes to swell back once the input quietens.
+    pub fn set_duck_release_ms(&mut self, release_ms: f32) {
+        self.duck_release_ms = release_ms.clamp(1.0, 5000.0);
+    }
+
+    // Renders the selected model and the early reflections
+    fn render(&self, input: &AudioData) -> AudioData {
+        let mut output = match self.model {
+            ReverbModel::Classic => self.process_classic(input),
+            ReverbModel::Freeverb => self.process_freeverb(input),
+        };
+        if self.er_level > 0.0 {
+            self.add_early_reflections(input, &mut output.samples);
+        }
+        output
+    }
+
+    // Renders the wet signal on its own, shapes it with the gate and ducking gains taken from the
+    // input level, and mixes it with the dry signal
+    fn render_with_dynamics(&self, input: &AudioData) -> AudioData {
+        let num_channels = input.num_channels;
+        let sample_rate = input.sample_rate as f32;
+        let mut wet = ReverbEffect {
+            wet_only: true,
+            ..self.clone()
+        }
+        .render(input);
+        let (dry_gain, wet_gain) = self.gains();
+
+        let mut detector = EnvelopeDetector::new(sample_rate, 0.0, DETECTOR_RELEASE_MS, DetectionMode::Peak);
+        let hold_frames = (self.gate_hold_ms / 1000.0 * sample_rate) as usize;
+        let (open, close) = (time_constant(GATE_OPEN_MS, sample_rate), time_constant(GATE_CLOSE_MS, sample_rate));
+        let mut hold = 0;
+        let mut gate_gain = 0.0_f32;
+        let mut ducker = GainComputer::new(sample_rate, GainMode::Compress, self.duck_threshold_db.unwrap_or(0.0), DUCK_RATIO);
+        ducker.set_range_db(self.duck_depth_db);
+        ducker.set_attack_ms(DUCK_ATTACK_MS);
+        ducker.set_release_ms(self.duck_release_ms);
+
+        for (i, frame) in wet.samples.chunks_mut(num_channels).enumerate() {
+            let dry = input.samples.get(i * num_channels..(i + 1) * num_channels);
+            let peak = dry.map_or(0.0, |dry| dry.iter().fold(0.0_f32, |max, s| max.max(s.abs())));
+            let level_db = detector.process(peak);
+
+            let mut gain = wet_gain;
+            if let Some(threshold_db) = self.gate_threshold_db {
+                if level_db >= threshold_db {
+                    hold = hold_frames + 1;
+                }
+                let (target, coeff) = if hold > 0 { (1.0, open) } else { (0.0, close) };
+                hold = hold.saturating_sub(1);
+                gate_gain = target + coeff * (gate_gain - target);
+                gain *= gate_gain;
+            }
+            if self.duck_threshold_db.is_some() {
+                gain *= db_to_linear(ducker.process(level_db));
+            }
+
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                *sample = dry.map_or(0.0, |dry| dry[channel] * dry_gain) + *sample * gain;
+            }
+        }
+
+        wet
+    }
+
+    // Mixes the early reflections into the wet part of `output`
+    fn add_early_reflections(&self, input: &AudioData, output: &mut [f32]) {
+        let num_channels = input.num_channels;
+        let sample_rate = input.sample_rate as f32;
+        let gain = self.er_level * self.gains().1;
+        let mut reflections: Vec<EarlyReflections> = (0..num_channels)
+            .map(|channel| EarlyReflections::new(sample_rate, self.er_pattern, self.er_size, channel))
+            .collect();
+
+        for (i, (sample, &dry)) in output.iter_mut().zip(&input.samples).enumerate() {
+            *sample += reflections[i % num_channels].process(dry) * gain;
+        }
+    }
+
+    // Time for the tail to fall by DECAY_DB after the input stops: the loop gain per pass around
+    // the longest feedback path sets the number of passes, plus the last early reflection
+    fn decay_secs(&self) -> f32 {
+        let (loop_gain, loop_secs) = match self.model {
+            // The classic lines are counted at 44.1 kHz and grow over long inputs, so this is
+            // the shortest the tail can be
+            ReverbModel::Classic => (FEEDBACK, (DELAY_TIME_MS / 1000.0) as f32),
+            ReverbModel::Freeverb => {
+                let longest = FREEVERB_COMBS[FREEVERB_COMBS.len() - 1] + STEREO_SPREAD;
+                let modulation_secs = self.modulation * MAX_MODULATION_MS / 1000.0;
+                (self.room_size * ROOM_SCALE + ROOM_OFFSET, longest as f32 / SAMPLE_RATE as f32 + modulation_secs)
+            }
+        };
+        let passes = DECAY_DB / -linear_to_db(loop_gain);
+        let reflections_secs = if self.er_level > 0.0 {
+            self.er_pattern.length_ms() * self.er_size / 1000.0
+        } else {
+            0.0
+        };
+        passes * loop_secs + reflections_secs
+    }
+
+    fn gains(&self) -> (f32, f32) {
+        if self.wet_only {
+            (0.0, 1.0)
+        } else {
+            (1.0 - WET_DRY_MIX, WET_DRY_MIX)
+        }
+    }
+
+    fn process_freeverb(&self, input: &AudioData) -> AudioData {
+        let num_channels = input.num_channels;
+        let sample_rate = input.sample_rate as f32;
+        let feedback = self.room_size * ROOM_SCALE + ROOM_OFFSET;
+        let damping = self.damping * DAMPING_SCALE;
+        let mut channels: Vec<FreeverbChannel> = (0..num_channels)
+            .map(|channel| FreeverbChannel::new(sample_rate, channel * STEREO_SPREAD, feedback, damping, self.modulation))
+            .collect();
+
+        let (dry_gain, wet_gain) = self.gains();
+
+        let mut wet = vec![0.0_f32; num_channels];
+        let mut output = Vec::with_capacity(input.samples.len());
+        for dry in input.samples.chunks_exact(num_channels) {
+            let mono = dry.iter().sum::<f32>() / num_channels as f32;
+            for (channel, tank) in channels.iter_mut().enumerate() {
+                wet[channel] = tank.process(mono * FREEVERB_INPUT_GAIN) * FREEVERB_WET_GAIN * wet_gain;
+            }
+            if num_channels == 2 && self.width != 1.0 {
+                let correction = (wet[0] - wet[1]) / 2.0 * (self.width - 1.0);
+                wet[0] += correction;
+                wet[1] -= correction;
+            }
+            for channel in 0..num_channels {
+                output.push(dry[channel] * dry_gain + wet[channel]);
+            }
+        }
+
+        AudioData::new(output, input.sample_rate, num_channels)
+    }
+
+    fn process_classic(&self, input: &AudioData) -> AudioData {
+        let samples = &input.samples;
+        let num_channels = input.num_channels;
+        let width = self.width;
+
+        // Initialize multiple delay line buffers with variable lengths
+        let mut delay_lengths = [
+            (SAMPLE_RATE as f64 * (DELAY_TIME_MS / 1000.0)) as usize,
+            (SAMPLE_RATE as f64 * (DELAY_TIME_MS / 1200.0)) as usize,
+            (SAMPLE_RATE as f64 * (DELAY_TIME_MS / 1400.0)) as usize,
+        ];
+        let mut delay_lines: Vec<Vec<f32>> = delay_lengths
+            .iter()
+            .map(|&length| vec![0.0; length * num_channels])
+            .collect();
+        let mut damping: Vec<f32> = delay_lengths
+            .iter()
+            .map(|&length| damping_coefficient(length, self.hf_decay_s))
+            .collect();
+        let mut damping_states = vec![vec![0.0_f32; num_channels]; NUM_DELAY_LINES];
+
+        let (dry_gain, wet_gain) = self.gains();
+
+        let mut frame_wet = vec![0.0_f32; num_channels];
+
+        // Process samples and apply reverb
+        let mut skip_block = false;
+        let mut output: Vec<f32> = Vec::with_capacity(samples.len());
+        for (i, &input_sample) in samples.iter().enumerate() {
+            let channel = i % num_channels;
+
+            // Blocks of silent input skip the DSP entirely once the reverb tail has died out
+            if i % SILENCE_BLOCK_SIZE == 0 {
+                let block = &samples[i..(i + SILENCE_BLOCK_SIZE).min(samples.len())];
+                skip_block = block.iter().all(|s| s.abs() < SILENCE_THRESHOLD)
+                    && delay_lines.iter().flatten().all(|x| x.abs() < SILENCE_THRESHOLD);
+                if skip_block {
+                    for delay_line in delay_lines.iter_mut() {
+                        delay_line.fill(0.0);
+                    }
+                    for states in damping_states.iter_mut() {
+                        states.fill(0.0);
+                    }
+                }
+            }
+
+            // Update delay lengths periodically
+            if i % (SAMPLE_RATE as usize * num_channels) == 0 {
+                // You can use user input, an algorithm, or any other method to update delay_lengths
+                // For demonstration purposes, we simply increase each delay length by 100 samples
+                for (j, delay_length) in delay_lengths.iter_mut().enumerate() {
+                    *delay_length += 100;
+
+                    // Update delay_lines with the new delay lengths
+                    let new_length = *delay_length * num_channels;
+                    delay_lines[j].resize(new_length, 0.0);
+                    damping[j] = damping_coefficient(*delay_length, self.hf_decay_s);
+                }
+            }
+
+            if skip_block {
+                output.push(0.0);
+                continue;
+            }
+
+            // Process each delay line
+            let mut wet_sample = 0.0;
+            let mut delayed_sum = 0.0;
+            for (j, delay_line) in delay_lines.iter_mut().enumerate() {
+                // Index `k * num_channels + channel` holds this channel's sample from k frames ago, and
+                // index `channel` the oldest one
+                let spread = (channel * STEREO_SPREAD).min(delay_lengths[j] - 1);
+                let read_index = if spread == 0 {
+                    channel
+                } else {
+                    (delay_lengths[j] - spread) * num_channels + channel
+                };
+                let delayed_sample = delay_line[read_index];
+                delayed_sum += delayed_sample;
+
+                // Combine input and delayed samples
+                wet_sample += input_sample * dry_gain + delayed_sample * wet_gain;
+
+                // Low-pass the feedback with this line's own filter state
+                let state = &mut damping_states[j][channel];
+                *state = flush_denormal(delayed_sample * (1.0 - damping[j]) + *state * damping[j]);
+
+                // Update delay line with feedback
+                let delay_input = input_sample + *state * FEEDBACK;
+                delay_line[channel] = flush_denormal(delay_input);
+
+                // Shift delay line once per frame, so each channel's samples keep their own slots
+                if channel == num_channels - 1 {
+                    delay_line.rotate_right(num_channels);
+                }
+            }
+
+            wet_sample /= NUM_DELAY_LINES as f32; // Normalize the wet_sample
+            output.push(wet_sample);
+
+            // Once a stereo frame is complete, rebalance its wet mid and side components
+            frame_wet[channel] = delayed_sum / NUM_DELAY_LINES as f32 * wet_gain;
+            if num_channels == 2 && channel == 1 && width != 1.0 {
+                let side = (frame_wet[0] - frame_wet[1]) / 2.0;
+                let correction = side * (width - 1.0);
+                output[i - 1] += correction;
+                output[i] -= correction;
+            }
+        }
+
+        AudioData::new(output, input.sample_rate, num_channels)
+    }
+}
+
+impl AudioEffect for ReverbEffect {
+    fn name(&self) -> &str {
+        "reverb"
+    }
+
+    fn tail_secs(&self) -> f32 {
+        self.decay_secs()
+    }
+
+    fn process(&mut self, input: &AudioData) -> AudioData {
+        if self.gate_threshold_db.is_some() || self.duck_threshold_db.is_some() {
+            self.render_with_dynamics(input)
+        } else {
+            self.render(input)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shorter_hf_decay_damps_harder() {
+        let length = SAMPLE_RATE as usize / 5;
+        let bright = damping_coefficient(length, 30.0);
+        let default = damping_coefficient(length, DEFAULT_HF_DECAY_S);
+        let dark = damping_coefficient(length, 0.1);
+        assert!(bright < default && default < dark, "{} {} {}", bright, default, dark);
+        // The loop gain at Nyquist is FEEDBACK * (1 - d) / (1 + d): -60 dB over the decay time
+        let loop_gain = FEEDBACK * (1.0 - default) / (1.0 + default);
+        let decay_db = 20.0 * loop_gain.log10() * DEFAULT_HF_DECAY_S / 0.2;
+        assert!((decay_db + 60.0).abs() < 0.01, "decay {} dB", decay_db);
+    }
+
+    #[test]
+    fn tail_estimate_follows_the_decay() {
+        let mut reverb = ReverbEffect::new();
+        // Each pass through a 200 ms line at a feedback of 0.5 loses 6 dB
+        assert!((reverb.tail_secs() - 1.993).abs() < 0.01, "{}", reverb.tail_secs());
+
+        reverb.set_model(ReverbModel::Freeverb);
+        let default = reverb.tail_secs();
+        reverb.set_room_size(1.0);
+        let large = reverb.tail_secs();
+        reverb.set_er_level(1.0);
+        reverb.set_er_pattern(ReflectionPattern::Hall);
+        let with_reflections = reverb.tail_secs();
+        assert!(default > 1.0 && default < 2.0, "{}", default);
+        assert!(large > 10.0, "{}", large);
+        assert!((with_reflections - large - ReflectionPattern::Hall.length_ms() / 1000.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn output_has_the_input_length() {
+        // The tail is rendered by the caller padding the input, never by the effect itself
+        let mut impulse = vec![0.0; 2 * 4410];
+        impulse[0] = 1.0;
+        let input = AudioData::new(impulse, 44100, 2);
+        for model in [ReverbModel::Classic, ReverbModel::Freeverb] {
+            let mut reverb = ReverbEffect::new();
+            reverb.set_model(model);
+            reverb.set_er_level(1.0);
+            let output = reverb.process(&input);
+            assert_eq!(output.samples.len(), input.samples.len());
+            assert!(output.samples.iter().all(|sample| sample.is_finite() && sample.abs() < 2.0));
+        }
+    }
+}
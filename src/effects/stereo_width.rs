@@ -0,0 +1,132 @@
+/*
+Mid/side stereo width.
+Left and right are converted to mid (L + R) / 2 and side (L - R) / 2, the two are scaled
+independently and converted back. The width scales the side on top of its own gain: 0 folds the
+signal to mono, 1 leaves it unchanged and 2 doubles the side. Bass mono high-passes the side with
+a 4th-order Linkwitz-Riley crossover, so everything below the frequency ends up in the centre.
+Only stereo input has a side component; other channel counts are returned unchanged.
+ */
+use super::AudioEffect;
+use crate::audio_io::AudioData;
+use crate::dsp::envelope::db_to_linear;
+use crate::dsp::{Crossover, CrossoverOrder};
+
+#[derive(Debug, Clone)]
+pub struct StereoWidthEffect {
+    width: f32,
+    mid_gain: f32,
+    side_gain: f32,
+    bass_mono_frequency: Option<f32>,
+}
+
+impl Default for StereoWidthEffect {
+    fn default() -> Self {
+        StereoWidthEffect::new()
+    }
+}
+
+impl StereoWidthEffect {
+    pub fn new() -> Self {
+        StereoWidthEffect {
+            width: 1.0,
+            mid_gain: 1.0,
+            side_gain: 1.0,
+            bass_mono_frequency: None,
+        }
+    }
+
+    /// Width from 0 (mono) through 1 (unchanged) to 2 (twice the side level).
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width.clamp(0.0, 2.0);
+    }
+
+    pub fn set_mid_gain_db(&mut self, gain_db: f32) {
+        self.mid_gain = db_to_linear(gain_db);
+    }
+
+    pub fn set_side_gain_db(&mut self, gain_db: f32) {
+        self.side_gain = db_to_linear(gain_db);
+    }
+
+    /// Removes the side component below `frequency` in Hz, or nothing with `None`.
+    pub fn set_bass_mono(&mut self, frequency: Option<f32>) {
+        self.bass_mono_frequency = frequency;
+    }
+}
+
+impl AudioEffect for StereoWidthEffect {
+    fn name(&self) -> &str {
+        "stereo width"
+    }
+
+    fn process(&mut self, input: &AudioData) -> AudioData {
+        if input.num_channels != 2 {
+            return input.clone();
+        }
+
+        let side_gain = self.side_gain * self.width;
+        let mut bass_mono = self
+            .bass_mono_frequency
+            .map(|frequency| Crossover::new(input.sample_rate as f32, frequency, CrossoverOrder::Fourth));
+
+        let mut samples = Vec::with_capacity(input.samples.len());
+        for frame in input.samples.chunks_exact(2) {
+            let mid = (frame[0] + frame[1]) * 0.5 * self.mid_gain;
+            let mut side = (frame[0] - frame[1]) * 0.5;
+            if let Some(crossover) = bass_mono.as_mut() {
+                side = crossover.process(side).1;
+            }
+            side *= side_gain;
+            samples.push(mid + side);
+            samples.push(mid - side);
+        }
+
+        AudioData::new(samples, input.sample_rate, 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::test_util::{assert_close, sine, SAMPLE_RATE};
+
+    fn wide(frames: usize, frequency: f32) -> AudioData {
+        // Left only: equal mid and side
+        let samples = (0..frames).flat_map(|n| [sine(frequency, n), 0.0]).collect();
+        AudioData::new(samples, SAMPLE_RATE as u32, 2)
+    }
+
+    #[test]
+    fn unit_width_is_transparent() {
+        let input = wide(100, 1000.0);
+        let output = StereoWidthEffect::new().process(&input);
+        for (actual, expected) in output.samples.iter().zip(&input.samples) {
+            assert_close(*actual, *expected, 1e-6);
+        }
+    }
+
+    #[test]
+    fn zero_width_folds_to_mono() {
+        let mut width = StereoWidthEffect::new();
+        width.set_width(0.0);
+        let output = width.process(&wide(100, 1000.0));
+        for frame in output.samples.chunks_exact(2) {
+            assert_eq!(frame[0], frame[1]);
+        }
+    }
+
+    #[test]
+    fn bass_mono_removes_only_low_side() {
+        let mut width = StereoWidthEffect::new();
+        width.set_bass_mono(Some(200.0));
+        let side_db = |frequency: f32| {
+            let output = width.clone().process(&wide(SAMPLE_RATE as usize, frequency));
+            let tail = &output.samples[output.samples.len() / 2..];
+            let side: f32 = tail.chunks_exact(2).map(|frame| ((frame[0] - frame[1]) / 2.0).powi(2)).sum();
+            10.0 * (side / (tail.len() / 2) as f32 * 2.0).log10()
+        };
+        // The side of a left-only sine is half its amplitude
+        assert!(side_db(40.0) < -40.0, "{}", side_db(40.0));
+        assert_close(side_db(5000.0), -6.02, 0.1);
+    }
+}
@@ -0,0 +1,173 @@
+/*
+A tape machine: soft saturation, a gentle high-frequency rolloff, and the pitch wobble of an
+imperfect transport.
+Saturation drives the signal into a tanh curve and scales it back down by the same gain, so
+quiet material passes at unity while peaks are rounded off more the harder it is driven. The
+wobble comes from reading the signal out of a delay line whose delay is modulated: wow is a slow
+random drift, flutter a faster sine with a little random jitter. Changing the delay changes the
+playback speed, which is what bends the pitch. The delay is centred on its modulation depth, and
+the effect reads that far ahead so the output stays aligned with the input. A low-pass biquad
+rounds off the top end last.
+All channels share one transport, so the wobble is the same on every channel.
+ */
+use super::AudioEffect;
+use crate::audio_io::AudioData;
+use crate::dsp::envelope::db_to_linear;
+use crate::dsp::{Biquad, DelayLine, FilterType, Lfo, SmoothRandom, Waveform};
+
+const DEFAULT_DRIVE_DB: f32 = 6.0;
+const DEFAULT_ROLLOFF_HZ: f32 = 12_000.0;
+const DEFAULT_WOW: f32 = 0.5;
+const DEFAULT_FLUTTER: f32 = 0.5;
+const WOW_RATE_HZ: f32 = 0.8;
+const FLUTTER_RATE_HZ: f32 = 7.0;
+const MAX_WOW_MS: f32 = 2.0; // delay swing at full wow depth
+const MAX_FLUTTER_MS: f32 = 0.1; // delay swing at full flutter depth
+const FLUTTER_JITTER: f32 = 0.3; // share of the flutter that is random
+const ROLLOFF_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+const WOW_SEED: u32 = 0x7A9E_0001;
+const FLUTTER_SEED: u32 = 0x7A9E_0002;
+
+#[derive(Debug, Clone)]
+pub struct TapeEffect {
+    drive: f32,
+    rolloff_hz: f32,
+    wow: f32,
+    flutter: f32,
+}
+
+impl Default for TapeEffect {
+    fn default() -> Self {
+        TapeEffect::new()
+    }
+}
+
+impl TapeEffect {
+    pub fn new() -> Self {
+        TapeEffect {
+            drive: db_to_linear(DEFAULT_DRIVE_DB),
+            rolloff_hz: DEFAULT_ROLLOFF_HZ,
+            wow: DEFAULT_WOW,
+            flutter: DEFAULT_FLUTTER,
+        }
+    }
+
+    /// Gain into the saturation curve in dB, clamped to 0-24 dB.
+    pub fn set_drive_db(&mut self, drive_db: f32) {
+        self.drive = db_to_linear(drive_db.clamp(0.0, 24.0));
+    }
+
+    /// Cutoff of the high-frequency rolloff in Hz.
+    pub fn set_rolloff_hz(&mut self, rolloff_hz: f32) {
+        self.rolloff_hz = rolloff_hz.max(1000.0);
+    }
+
+    /// Depth of the slow pitch drift, from 0 to 1.
+    pub fn set_wow(&mut self, wow: f32) {
+        self.wow = wow.clamp(0.0, 1.0);
+    }
+
+    /// Depth of the fast pitch modulation, from 0 to 1.
+    pub fn set_flutter(&mut self, flutter: f32) {
+        self.flutter = flutter.clamp(0.0, 1.0);
+    }
+}
+
+impl AudioEffect for TapeEffect {
+    fn name(&self) -> &str {
+        "tape"
+    }
+
+    fn process(&mut self, input: &AudioData) -> AudioData {
+        let num_channels = input.num_channels;
+        let num_frames = input.num_frames();
+        let sample_rate = input.sample_rate as f32;
+
+        let wow_depth = self.wow * MAX_WOW_MS / 1000.0 * sample_rate;
+        let flutter_depth = self.flutter * MAX_FLUTTER_MS / 1000.0 * sample_rate;
+        // The modulated delay swings around this many samples and never drops below one
+        let lead = (1.0 + wow_depth + flutter_depth).ceil() as usize;
+
+        let mut lines = vec![DelayLine::new(2 * lead + 2); num_channels];
+        let mut rolloff = vec![Biquad::with_type(FilterType::LowPass, sample_rate, self.rolloff_hz, ROLLOFF_Q); num_channels];
+        let mut wow = SmoothRandom::new(sample_rate, WOW_RATE_HZ, WOW_SEED);
+        let mut flutter = Lfo::new(sample_rate, FLUTTER_RATE_HZ, Waveform::Sine);
+        let mut jitter = SmoothRandom::new(sample_rate, FLUTTER_RATE_HZ, FLUTTER_SEED);
+
+        let mut samples = Vec::with_capacity(input.samples.len());
+        // Run `lead` frames ahead of the output, so the centre delay cancels out
+        for frame in 0..num_frames + lead {
+            let flutter_value = (1.0 - FLUTTER_JITTER) * flutter.next_value() + FLUTTER_JITTER * jitter.next_value();
+            let delay = lead as f32 + wow_depth * wow.next_value() + flutter_depth * flutter_value;
+
+            for channel in 0..num_channels {
+                let dry = input.samples.get(frame * num_channels + channel).copied().unwrap_or(0.0);
+                if frame >= lead {
+                    let wobbled = lines[channel].read_interpolated(delay);
+                    samples.push(rolloff[channel].process(wobbled));
+                }
+                lines[channel].write((self.drive * dry).tanh() / self.drive);
+            }
+        }
+
+        AudioData::new(samples, input.sample_rate, num_channels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn impulse(frames: usize, at: usize) -> AudioData {
+        let mut samples = vec![0.0; frames];
+        samples[at] = 0.5;
+        AudioData::new(samples, 48000, 1)
+    }
+
+    fn peak_index(samples: &[f32]) -> usize {
+        (0..samples.len()).max_by(|&a, &b| samples[a].abs().total_cmp(&samples[b].abs())).unwrap()
+    }
+
+    #[test]
+    fn output_stays_aligned_with_the_input() {
+        let mut tape = TapeEffect::new();
+        tape.set_wow(0.0);
+        tape.set_flutter(0.0);
+        let input = impulse(1000, 300);
+        let output = tape.process(&input);
+        assert_eq!(output.num_frames(), 1000);
+        assert_eq!(tape.latency_samples(), 0);
+
+        // Without wobble the transport adds no delay: only the saturation and the rolloff remain
+        let drive = db_to_linear(DEFAULT_DRIVE_DB);
+        let mut rolloff = Biquad::with_type(FilterType::LowPass, 48000.0, DEFAULT_ROLLOFF_HZ, ROLLOFF_Q);
+        for (actual, dry) in output.samples.iter().zip(&input.samples) {
+            assert_eq!(*actual, rolloff.process((drive * dry).tanh() / drive));
+        }
+    }
+
+    #[test]
+    fn wobble_stays_close_and_bounded() {
+        let mut tape = TapeEffect::new();
+        tape.set_wow(1.0);
+        tape.set_flutter(1.0);
+        tape.set_drive_db(24.0);
+        let output = tape.process(&impulse(48000, 24000));
+        assert_eq!(output.num_frames(), 48000);
+        assert!(output.samples.iter().all(|s| s.is_finite() && s.abs() < 1.0));
+        // Full wow swings the delay by at most 2 ms either way
+        assert!(peak_index(&output.samples).abs_diff(24000) <= 96);
+    }
+
+    #[test]
+    fn saturation_rounds_off_peaks() {
+        let mut tape = TapeEffect::new();
+        tape.set_wow(0.0);
+        tape.set_flutter(0.0);
+        tape.set_drive_db(24.0);
+        let loud = AudioData::new(vec![1.0; 4800], 48000, 1);
+        let output = tape.process(&loud);
+        let level = output.samples[4000];
+        assert!(level < 0.1, "{}", level);
+    }
+}
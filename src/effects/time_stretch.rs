@@ -0,0 +1,99 @@
+/*
+Time stretching: changes the duration of the input without changing its pitch, with the phase
+vocoder in `dsp::phase_vocoder`. A stretch of 2 doubles the length, 0.5 halves it. Each channel
+is stretched on its own, and a stretch of exactly 1 passes the input through untouched.
+Unlike most effects, the output can be shorter than the input.
+ */
+use super::AudioEffect;
+use crate::audio_io::AudioData;
+use crate::dsp::PhaseVocoder;
+
+#[derive(Debug, Clone)]
+pub struct TimeStretchEffect {
+    stretch: f32,
+}
+
+impl Default for TimeStretchEffect {
+    fn default() -> Self {
+        TimeStretchEffect::new()
+    }
+}
+
+impl TimeStretchEffect {
+    pub fn new() -> Self {
+        TimeStretchEffect { stretch: 1.0 }
+    }
+
+    /// Output length as a multiple of the input length, clamped to 0.25-4.
+    pub fn set_stretch(&mut self, stretch: f32) {
+        self.stretch = stretch.clamp(0.25, 4.0);
+    }
+
+    pub fn stretch(&self) -> f32 {
+        self.stretch
+    }
+}
+
+impl AudioEffect for TimeStretchEffect {
+    fn name(&self) -> &str {
+        "time-stretch"
+    }
+
+    fn process(&mut self, input: &AudioData) -> AudioData {
+        if self.stretch == 1.0 {
+            return input.clone();
+        }
+        let num_channels = input.num_channels;
+        let vocoder = PhaseVocoder::new();
+
+        let channels: Vec<Vec<f32>> = (0..num_channels)
+            .map(|channel| {
+                let samples: Vec<f32> = input.samples.iter().skip(channel).step_by(num_channels).copied().collect();
+                vocoder.process(&samples, self.stretch as f64)
+            })
+            .collect();
+        let num_frames = channels.first().map_or(0, Vec::len);
+        let samples = (0..num_frames)
+            .flat_map(|frame| channels.iter().map(move |channel| channel[frame]))
+            .collect();
+
+        AudioData::new(samples, input.sample_rate, num_channels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::test_util::sine;
+
+    #[test]
+    fn output_length_follows_the_stretch() {
+        let samples = (0..4800).flat_map(|n| [sine(440.0, n), sine(440.0, n)]).collect();
+        let input = AudioData::new(samples, 48000, 2);
+        let mut stretch = TimeStretchEffect::new();
+        stretch.set_stretch(1.5);
+        let output = stretch.process(&input);
+        assert_eq!(output.num_channels, 2);
+        assert_eq!(output.num_frames(), 7200);
+        // Both channels are stretched the same way
+        assert!(output.samples.chunks_exact(2).all(|frame| frame[0] == frame[1]));
+
+        stretch.set_stretch(0.5);
+        assert_eq!(stretch.process(&input).num_frames(), 2400);
+    }
+
+    #[test]
+    fn unit_stretch_is_transparent() {
+        let input = AudioData::new(vec![0.5, -0.25, 0.125], 48000, 1);
+        assert_eq!(TimeStretchEffect::new().process(&input), input);
+    }
+
+    #[test]
+    fn stretch_is_clamped() {
+        let mut stretch = TimeStretchEffect::new();
+        stretch.set_stretch(10.0);
+        assert_eq!(stretch.stretch(), 4.0);
+        stretch.set_stretch(0.0);
+        assert_eq!(stretch.stretch(), 0.25);
+    }
+}
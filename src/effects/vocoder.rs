@@ -0,0 +1,220 @@
+/*
+A channel vocoder: the input (the modulator, typically a voice) imposes its spectral envelope on
+a carrier (a synth, a second recording, or the built-in sawtooth or noise).
+Both signals are split into the same log-spaced band-pass bands between 100 Hz and 8 kHz, each
+band a pair of cascaded band-pass biquads with a Q that makes neighbouring bands meet. The level
+of every modulator band is followed with a fast attack and slower release, and scales the
+matching carrier band; the scaled bands are summed. The carrier sets the pitch, the modulator
+the articulation. Since the summed level depends on the carrier's spectrum, the output is scaled
+to the modulator's RMS level.
+A carrier file is resampled to the input's rate if needed and looped when it is shorter. Each
+input channel uses the carrier channel with the same index (wrapping around).
+ */
+use super::AudioEffect;
+use crate::audio_io::AudioData;
+use crate::dsp::envelope::time_constant;
+use crate::dsp::{resample, Biquad, FilterType, Lfo, ResamplerQuality, Waveform, WhiteNoise};
+
+const LOWEST_BAND_HZ: f32 = 100.0;
+const HIGHEST_BAND_HZ: f32 = 8000.0;
+const MIN_BANDS: usize = 8;
+const MAX_BANDS: usize = 32;
+const DEFAULT_BANDS: usize = 16;
+const ATTACK_MS: f32 = 5.0;
+const RELEASE_MS: f32 = 50.0;
+const NOISE_SEED: u32 = 0x5EED_CAFE;
+
+/// The signal the modulator's envelope is applied to.
+#[derive(Debug, Clone)]
+pub enum Carrier {
+    /// A sawtooth at this frequency in Hz.
+    Saw(f32),
+    /// White noise, for a whispered sound.
+    Noise,
+    /// A recording, looped to the input's length.
+    Audio(AudioData),
+}
+
+#[derive(Debug, Clone)]
+pub struct VocoderEffect {
+    carrier: Carrier,
+    num_bands: usize,
+}
+
+// One band-pass band: two cascaded biquads, steeper than one
+#[derive(Debug, Clone)]
+struct Band([Biquad; 2]);
+
+impl Band {
+    fn new(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let section = Biquad::with_type(FilterType::BandPass, sample_rate, frequency, q);
+        Band([section.clone(), section])
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let first = self.0[0].process(input);
+        self.0[1].process(first)
+    }
+}
+
+impl VocoderEffect {
+    pub fn new(carrier: Carrier) -> Self {
+        VocoderEffect {
+            carrier,
+            num_bands: DEFAULT_BANDS,
+        }
+    }
+
+    pub fn set_carrier(&mut self, carrier: Carrier) {
+        self.carrier = carrier;
+    }
+
+    /// Sets the number of analysis bands, clamped to 8-32.
+    pub fn set_num_bands(&mut self, num_bands: usize) {
+        self.num_bands = num_bands.clamp(MIN_BANDS, MAX_BANDS);
+    }
+
+    // Builds the carrier for `num_frames` frames at `sample_rate`
+    fn render_carrier(&self, num_frames: usize, sample_rate: u32) -> AudioData {
+        match &self.carrier {
+            Carrier::Saw(frequency) => {
+                let mut oscillator = Lfo::new(sample_rate as f32, *frequency, Waveform::Saw);
+                let samples = (0..num_frames).map(|_| oscillator.next_value()).collect();
+                AudioData::new(samples, sample_rate, 1)
+            }
+            Carrier::Noise => {
+                let mut noise = WhiteNoise::new(NOISE_SEED);
+                let samples = (0..num_frames).map(|_| noise.next_sample()).collect();
+                AudioData::new(samples, sample_rate, 1)
+            }
+            Carrier::Audio(audio) => {
+                let num_channels = audio.num_channels;
+                let samples = resample(
+                    &audio.samples,
+                    num_channels,
+                    audio.sample_rate,
+                    sample_rate,
+                    ResamplerQuality::default(),
+                );
+                let samples = if samples.is_empty() {
+                    vec![0.0; num_frames * num_channels]
+                } else {
+                    samples.iter().copied().cycle().take(num_frames * num_channels).collect()
+                };
+                AudioData::new(samples, sample_rate, num_channels)
+            }
+        }
+    }
+}
+
+// Log-spaced centre frequencies, and the Q that makes neighbouring bands cross at -3 dB
+fn band_layout(num_bands: usize, sample_rate: f32) -> (Vec<f32>, f32) {
+    let highest = HIGHEST_BAND_HZ.min(0.45 * sample_rate);
+    let ratio = (highest / LOWEST_BAND_HZ).powf(1.0 / (num_bands - 1) as f32);
+    let frequencies = (0..num_bands)
+        .map(|band| LOWEST_BAND_HZ * ratio.powi(band as i32))
+        .collect();
+    (frequencies, ratio.sqrt() / (ratio - 1.0))
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    let sum: f64 = samples.iter().map(|&s| (s as f64).powi(2)).sum();
+    (sum / samples.len().max(1) as f64).sqrt() as f32
+}
+
+impl AudioEffect for VocoderEffect {
+    fn name(&self) -> &str {
+        "vocoder"
+    }
+
+    fn process(&mut self, input: &AudioData) -> AudioData {
+        let num_channels = input.num_channels;
+        let num_frames = input.num_frames();
+        let sample_rate = input.sample_rate as f32;
+        let carrier = self.render_carrier(num_frames, input.sample_rate);
+        let (frequencies, q) = band_layout(self.num_bands, sample_rate);
+        let attack = time_constant(ATTACK_MS, sample_rate);
+        let release = time_constant(RELEASE_MS, sample_rate);
+
+        let mut samples = vec![0.0_f32; input.samples.len()];
+        for channel in 0..num_channels {
+            let carrier_channel = channel % carrier.num_channels;
+            let bands: Vec<Band> = frequencies.iter().map(|&f| Band::new(sample_rate, f, q)).collect();
+            let mut modulator_bands = bands.clone();
+            let mut carrier_bands = bands;
+            let mut envelopes = vec![0.0_f32; frequencies.len()];
+
+            for frame in 0..num_frames {
+                let modulator = input.samples[frame * num_channels + channel];
+                let carrier_sample = carrier.samples[frame * carrier.num_channels + carrier_channel];
+                let mut sum = 0.0;
+                for band in 0..frequencies.len() {
+                    let level = modulator_bands[band].process(modulator).abs();
+                    let coefficient = if level > envelopes[band] { attack } else { release };
+                    envelopes[band] = coefficient * envelopes[band] + (1.0 - coefficient) * level;
+                    sum += carrier_bands[band].process(carrier_sample) * envelopes[band];
+                }
+                samples[frame * num_channels + channel] = sum;
+            }
+        }
+
+        let (input_rms, output_rms) = (rms(&input.samples), rms(&samples));
+        if output_rms > 0.0 {
+            let gain = input_rms / output_rms;
+            samples.iter_mut().for_each(|s| *s *= gain);
+        }
+
+        AudioData::new(samples, input.sample_rate, num_channels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::test_util::{assert_close, sine};
+
+    fn voice(frames: usize) -> AudioData {
+        // A tone switched on and off, so the envelope has something to follow
+        let samples = (0..frames)
+            .map(|n| if (n / 4800) % 2 == 0 { 0.5 * sine(300.0, n) } else { 0.0 })
+            .collect();
+        AudioData::new(samples, 48000, 1)
+    }
+
+    #[test]
+    fn output_matches_the_modulator_level() {
+        let input = voice(48000);
+        for carrier in [Carrier::Saw(110.0), Carrier::Noise] {
+            let output = VocoderEffect::new(carrier).process(&input);
+            assert_eq!(output.samples.len(), input.samples.len());
+            assert!(output.samples.iter().all(|s| s.is_finite()));
+            assert_close(rms(&output.samples), rms(&input.samples), 1e-4);
+        }
+    }
+
+    #[test]
+    fn silence_stays_silent() {
+        let input = AudioData::new(vec![0.0; 4800], 48000, 2);
+        let output = VocoderEffect::new(Carrier::Saw(110.0)).process(&input);
+        assert!(output.samples.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn short_carrier_files_are_looped_and_resampled() {
+        let carrier = AudioData::new((0..2205).map(|n| sine(441.0, n)).collect(), 44100, 1);
+        let vocoder = VocoderEffect::new(Carrier::Audio(carrier));
+        let rendered = vocoder.render_carrier(10_000, 48000);
+        assert_eq!(rendered.sample_rate, 48000);
+        assert_eq!(rendered.samples.len(), 10_000);
+        assert_eq!(rendered.samples[..2400], rendered.samples[2400..4800]);
+    }
+
+    #[test]
+    fn bands_cover_the_range() {
+        let (frequencies, q) = band_layout(16, 48000.0);
+        assert_eq!(frequencies.len(), 16);
+        assert_close(frequencies[0], LOWEST_BAND_HZ, 1e-3);
+        assert_close(frequencies[15], HIGHEST_BAND_HZ, 0.5);
+        assert!(q > 1.0);
+    }
+}
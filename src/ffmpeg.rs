@@ -0,0 +1,97 @@
+/*
+Formats hound cannot read or write (M4A, MP3, FLAC, video containers) are handled by shelling out
+to ffmpeg. The input is decoded to a temporary 32-bit float WAV file, so it is neither quantized
+nor clipped on the way in; the effect runs on that as usual, and its WAV output is encoded to
+whatever format the output file's extension asks for.
+ffmpeg has to be installed and on the PATH; it is only used when --via-ffmpeg is given.
+ */
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Decoding always produces 32-bit float WAV, whatever the input container
+const DECODE_ARGS: &[&str] = &["-c:a", "pcm_f32le", "-f", "wav"];
+
+// Numbers the temporary files, so parallel batch jobs do not share them
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn temp_wav(name: &str) -> PathBuf {
+    let number = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("audiofxrs-{}-{}-{}.wav", process::id(), number, name))
+}
+
+// Quiet, overwriting conversion from `input` to `output`; `extra_args` go before the output path
+fn ffmpeg_command(input: &Path, output: &Path, extra_args: &[&str]) -> Command {
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-v", "error", "-y", "-i"])
+        .arg(input)
+        .args(extra_args)
+        .arg(output);
+    command
+}
+
+fn run_ffmpeg(input: &Path, output: &Path, extra_args: &[&str]) -> Result<(), String> {
+    let status = ffmpeg_command(input, output, extra_args)
+        .status()
+        .map_err(|e| format!("Failed to run ffmpeg, is it installed and on the PATH? ({})", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("ffmpeg failed to convert {} to {}", input.display(), output.display()))
+    }
+}
+
+/// Decodes `input` to a temporary 32-bit float WAV file and returns its path.
+pub fn decode(input: &str) -> Result<PathBuf, String> {
+    let wav = temp_wav("input");
+    run_ffmpeg(Path::new(input), &wav, DECODE_ARGS)?;
+    Ok(wav)
+}
+
+/// Returns a temporary WAV path for the effect to write to before `encode`.
+pub fn output_wav() -> PathBuf {
+    temp_wav("output")
+}
+
+/// Encodes the WAV file `wav` to `output` and removes `wav`.
+pub fn encode(wav: &Path, output: &str) -> Result<(), String> {
+    let result = run_ffmpeg(wav, Path::new(output), &[]);
+    let _ = std::fs::remove_file(wav);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    fn args(command: &Command) -> Vec<&OsStr> {
+        command.get_args().collect()
+    }
+
+    #[test]
+    fn decode_command_forces_float_wav() {
+        let command = ffmpeg_command(Path::new("in put.m4a"), Path::new("/tmp/out.wav"), DECODE_ARGS);
+        assert_eq!(command.get_program(), "ffmpeg");
+        assert_eq!(
+            args(&command),
+            ["-v", "error", "-y", "-i", "in put.m4a", "-c:a", "pcm_f32le", "-f", "wav", "/tmp/out.wav"]
+        );
+    }
+
+    #[test]
+    fn encode_command_leaves_the_format_to_the_extension() {
+        let command = ffmpeg_command(Path::new("/tmp/in.wav"), Path::new("out.flac"), &[]);
+        assert_eq!(args(&command), ["-v", "error", "-y", "-i", "/tmp/in.wav", "out.flac"]);
+    }
+
+    #[test]
+    fn temporary_files_are_unique() {
+        let first = temp_wav("input");
+        let second = temp_wav("input");
+        assert_ne!(first, second);
+        assert_eq!(first.extension().unwrap(), "wav");
+        assert!(first.starts_with(std::env::temp_dir()));
+    }
+}
@@ -0,0 +1,13 @@
+/*
+The audiofxrs library: the effects, file I/O and DSP behind the command line tool, usable from
+other Rust projects.
+`audio_io` reads and writes WAV files as `AudioData`, `effects` holds the `AudioEffect` trait and
+its implementations, and `dsp` the reusable signal processing primitives (filters, detectors,
+generators) that the effects are built from.
+ */
+pub mod audio_io;
+pub mod dsp;
+pub mod effects;
+
+pub use audio_io::AudioData;
+pub use effects::AudioEffect;
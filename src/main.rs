@@ -1,91 +1,484 @@
 // Import the required libraries and set constant values:
-use hound;
 use std::env;
 
-const SAMPLE_RATE: u32 = 44100;
-const DELAY_TIME_MS: f64 = 200.0;
-const FEEDBACK: f32 = 0.5;
-const WET_DRY_MIX: f32 = 0.5;
-const NUM_DELAY_LINES: usize = 3;
+use audiofxrs::audio_io;
+use audiofxrs::dsp::{ReflectionPattern, ResamplerQuality, Waveform};
+use audiofxrs::effects::gain::Normalize;
+use audiofxrs::effects::vocoder::Carrier;
+use audiofxrs::effects::{
+    GainEffect, GranularEffect, PanEffect, PitchShiftEffect, ResampleEffect, ReverbEffect, ReverbModel, StereoWidthEffect,
+    TapeEffect, TimeStretchEffect, VocoderEffect,
+};
 
-fn main() {
-    // Parse command line arguments for the output WAV file path:
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 3 {
-        eprintln!("Usage: {} <input.wav> <output.wav>", args[0]);
-        std::process::exit(1);
+mod analysis;
+mod batch;
+mod diff;
+mod ffmpeg;
+mod process;
+mod split;
+mod stats;
+mod tempo;
+
+use process::{CommonOptions, COMMON_USAGE};
+use tempo::Timing;
+
+const DEFAULT_WIDTH: f32 = 1.0;
+const DEFAULT_CARRIER_HZ: f32 = 110.0;
+
+fn print_usage(program: &str) -> ! {
+    eprintln!(
+        "Usage: {} <input.wav> <output.wav> [--wet-only] [--width <0-2>] [--hf-decay <s>] [--model classic|freeverb] \
+         [--room-size <0-1>] [--damping <0-1>] [--modulation <0-1>] [--er-pattern room|hall|plate] \
+         [--er-level <0-1>] [--er-size <0.25-2>] [--gate <dB>] [--gate-hold <ms | note>] [--duck <dB>] \
+         [--duck-depth <dB>] [--duck-release <ms | note>] {}",
+        program, COMMON_USAGE
+    );
+    eprintln!(
+        "       {} gain <input.wav> <output.wav> [--db <dB> | --linear <factor>] [--invert] [--remove-dc] \
+         [--normalize <dBFS> | --normalize-rms <dBFS> | --lufs <LUFS>] {}",
+        program, COMMON_USAGE
+    );
+    eprintln!(
+        "       {} stereo-width <input.wav> <output.wav> [--width <0-200%>] [--mid-db <dB>] [--side-db <dB>] \
+         [--bass-mono <Hz>] {}",
+        program, COMMON_USAGE
+    );
+    eprintln!(
+        "       {} pan <input.wav> <output.wav> [--position <-1-1>] [--auto <Hz | note>] [--depth <0-1>] \
+         [--waveform sine|triangle|saw|square|random] {}",
+        program, COMMON_USAGE
+    );
+    eprintln!(
+        "       {} resample <input.wav> <output.wav> --target-rate <Hz> [--quality fast|medium|high] {}",
+        program, COMMON_USAGE
+    );
+    eprintln!(
+        "       {} vocoder <input.wav> <output.wav> [--carrier <carrier.wav> | --saw <Hz> | --noise] [--bands <8-32>] {}",
+        program, COMMON_USAGE
+    );
+    eprintln!(
+        "       {} granular <input.wav> <output.wav> [--grain <ms | note>] [--density <grains/s>] [--spread <ms | note>] \
+         [--pitch <semitones>] [--pitch-spread <semitones>] [--reverse <0-1>] [--wet <0-1>] [--seed <n>] {}",
+        program, COMMON_USAGE
+    );
+    eprintln!(
+        "       {} tape <input.wav> <output.wav> [--drive <dB>] [--rolloff <Hz>] [--wow <0-1>] [--flutter <0-1>] {}",
+        program, COMMON_USAGE
+    );
+    eprintln!(
+        "       {} pitch-shift <input.wav> <output.wav> [--pitch <0.25-4> | --semitones <-24-24>] [--quality fast|medium|high] {}",
+        program, COMMON_USAGE
+    );
+    eprintln!("       {} time-stretch <input.wav> <output.wav> --stretch <0.25-4> {}", program, COMMON_USAGE);
+    eprintln!("       {} analyze {} <input.wav>", program, analysis::USAGE);
+    eprintln!("       {} stats <input.wav>", program);
+    eprintln!("       {} diff <a.wav> <b.wav> [--residual <output.wav>]", program);
+    eprintln!("       {} split <input.wav | -> <output_prefix> [--threshold <dB>] [--min-silence <seconds>]", program);
+    eprintln!();
+    eprintln!("Inputs may be WAV, MP3, FLAC or Ogg Vorbis files (other formats need --via-ffmpeg); output is WAV.");
+    eprintln!("Times and rates marked <note> also take note values such as 1/8, 1/8d or 1/4t with --bpm (or --bpm auto).");
+    eprintln!("Use - as <input.wav> or <output.wav> for stdin or stdout; --raw reads 16-bit PCM without a header.");
+    eprintln!("With --out-dir, effects take any number of input files or quoted glob patterns instead of <input.wav> <output.wav>;");
+    eprintln!("--jobs <n> processes that many of them at once.");
+    std::process::exit(1);
+}
+
+// Parses a level such as "-1", "-1dB" or "-1dBFS"
+fn parse_db(value: Option<&String>) -> f32 {
+    value
+        .and_then(|value| value.trim_end_matches("FS").trim_end_matches("dB").parse().ok())
+        .expect("Invalid level, expected a value in dB")
+}
+
+fn parse_waveform(value: Option<&String>) -> Waveform {
+    match value.map(String::as_str) {
+        Some("sine") => Waveform::Sine,
+        Some("triangle") => Waveform::Triangle,
+        Some("saw") => Waveform::Saw,
+        Some("square") => Waveform::Square,
+        Some("random") => Waveform::SampleAndHold,
+        _ => panic!("Invalid waveform, expected sine, triangle, saw, square or random"),
+    }
+}
+
+// Parses a time or rate in `unit` or as a note value, for the option named in `what`
+fn parse_timing(value: Option<&String>, unit: &str, what: &str) -> Timing {
+    value
+        .and_then(|value| Timing::parse(value, unit))
+        .unwrap_or_else(|| panic!("Invalid {}, expected a value in {} or a note value such as 1/8", what, unit))
+}
+
+// Parses a plain number for the option named in `what`
+fn parse_quality(value: Option<&String>) -> ResamplerQuality {
+    match value.map(String::as_str) {
+        Some("fast") => ResamplerQuality::Fast,
+        Some("medium") => ResamplerQuality::Medium,
+        Some("high") => ResamplerQuality::High,
+        _ => panic!("Invalid quality, expected fast, medium or high"),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: Option<&String>, what: &str) -> T {
+    value
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_else(|| panic!("Invalid {}, expected a number", what))
+}
+
+fn run_reverb(args: &[String]) {
+    let mut reverb = ReverbEffect::new();
+    reverb.set_width(DEFAULT_WIDTH);
+    let mut common = CommonOptions::default();
+    let mut gate_hold = None;
+    let mut duck_release = None;
+
+    let mut options = args[1..].iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--wet-only" => reverb.set_wet_only(true),
+            "--width" => {
+                let width = options
+                    .next()
+                    .and_then(|value| value.parse::<f32>().ok())
+                    .expect("Invalid width, expected a number between 0 and 2");
+                reverb.set_width(width);
+            }
+            "--hf-decay" => {
+                let secs = options
+                    .next()
+                    .and_then(|value| value.trim_end_matches('s').parse::<f32>().ok())
+                    .filter(|&secs| secs > 0.0)
+                    .expect("Invalid high-frequency decay, expected a time in seconds greater than 0");
+                reverb.set_hf_decay_secs(secs);
+            }
+            "--model" => match options.next().map(String::as_str) {
+                Some("classic") => reverb.set_model(ReverbModel::Classic),
+                Some("freeverb") => reverb.set_model(ReverbModel::Freeverb),
+                _ => panic!("Invalid model, expected classic or freeverb"),
+            },
+            "--room-size" => reverb.set_room_size(parse_number(options.next(), "room size")),
+            "--damping" => reverb.set_damping(parse_number(options.next(), "damping")),
+            "--modulation" => reverb.set_modulation(parse_number(options.next(), "modulation")),
+            "--er-pattern" => match options.next().map(String::as_str) {
+                Some("room") => reverb.set_er_pattern(ReflectionPattern::Room),
+                Some("hall") => reverb.set_er_pattern(ReflectionPattern::Hall),
+                Some("plate") => reverb.set_er_pattern(ReflectionPattern::Plate),
+                _ => panic!("Invalid early reflections pattern, expected room, hall or plate"),
+            },
+            "--er-level" => reverb.set_er_level(parse_number(options.next(), "early reflections level")),
+            "--er-size" => reverb.set_er_size(parse_number(options.next(), "early reflections size")),
+            "--gate" => reverb.set_gate(Some(parse_db(options.next()))),
+            "--gate-hold" => gate_hold = Some(parse_timing(options.next(), "ms", "gate hold time")),
+            "--duck" => reverb.set_ducking(Some(parse_db(options.next()))),
+            "--duck-depth" => reverb.set_duck_depth_db(parse_db(options.next())),
+            "--duck-release" => duck_release = Some(parse_timing(options.next(), "ms", "ducking release time")),
+            _ => common.parse(arg, &mut options),
+        }
+    }
+
+    if !common.is_valid() {
+        print_usage(&args[0]);
     }
+    common.resolve_bpm();
+    if let Some(gate_hold) = gate_hold {
+        reverb.set_gate_hold_ms(Timing::to_ms(gate_hold, common.bpm));
+    }
+    if let Some(duck_release) = duck_release {
+        reverb.set_duck_release_ms(Timing::to_ms(duck_release, common.bpm));
+    }
+    process::run(&mut reverb, &common);
+}
 
-    let input_file = &args[1];
-    let output_file = &args[2];
+fn run_gain(args: &[String]) {
+    let mut gain = GainEffect::new();
+    let mut common = CommonOptions::default();
 
-    // Read input WAV file
-    let mut reader = hound::WavReader::open(input_file).unwrap();
-    let samples: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
-    let num_channels = reader.spec().channels as usize;
+    let mut options = args[2..].iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--db" => gain.set_gain_db(parse_db(options.next())),
+            "--linear" => {
+                let factor = options
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .expect("Invalid gain, expected a linear factor");
+                gain.set_gain_linear(factor);
+            }
+            "--invert" => gain.set_invert(true),
+            "--remove-dc" => gain.set_remove_dc(true),
+            "--normalize" => gain.set_normalize(Some(Normalize::Peak(parse_db(options.next())))),
+            "--normalize-rms" => gain.set_normalize(Some(Normalize::Rms(parse_db(options.next())))),
+            "--lufs" => {
+                let target = options
+                    .next()
+                    .and_then(|value| value.trim_end_matches("LUFS").parse().ok())
+                    .expect("Invalid loudness target, expected a value in LUFS");
+                gain.set_normalize(Some(Normalize::Lufs(target)));
+            }
+            _ => common.parse(arg, &mut options),
+        }
+    }
 
-    // Prepare output WAV file
-    let spec = hound::WavSpec {
-        channels: reader.spec().channels,
-        sample_rate: reader.spec().sample_rate,
-        bits_per_sample: reader.spec().bits_per_sample,
-        sample_format: hound::SampleFormat::Int,
+    if !common.is_valid() {
+        print_usage(&args[0]);
+    }
+    process::run(&mut gain, &common);
+    if common.writes_stdout() {
+        eprintln!("Applied gain: {:+.2} dB", gain.applied_gain_db());
+    } else if common.out_dir.is_none() {
+        println!("Applied gain: {:+.2} dB", gain.applied_gain_db());
+    }
+}
+
+fn run_stereo_width(args: &[String]) {
+    let mut stereo_width = StereoWidthEffect::new();
+    let mut common = CommonOptions::default();
+
+    let mut options = args[2..].iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--width" => {
+                let percent = options
+                    .next()
+                    .and_then(|value| value.trim_end_matches('%').parse::<f32>().ok())
+                    .expect("Invalid width, expected a percentage between 0 and 200");
+                stereo_width.set_width(percent / 100.0);
+            }
+            "--mid-db" => stereo_width.set_mid_gain_db(parse_db(options.next())),
+            "--side-db" => stereo_width.set_side_gain_db(parse_db(options.next())),
+            "--bass-mono" => {
+                let frequency = options
+                    .next()
+                    .and_then(|value| value.trim_end_matches("Hz").parse::<f32>().ok())
+                    .filter(|&frequency| frequency > 0.0)
+                    .expect("Invalid bass mono frequency, expected a value in Hz");
+                stereo_width.set_bass_mono(Some(frequency));
+            }
+            _ => common.parse(arg, &mut options),
+        }
+    }
+
+    if !common.is_valid() {
+        print_usage(&args[0]);
+    }
+    process::run(&mut stereo_width, &common);
+}
+
+fn run_pan(args: &[String]) {
+    let mut pan = PanEffect::new();
+    let mut auto_pan = None;
+    let mut common = CommonOptions::default();
+
+    let mut options = args[2..].iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--position" => {
+                let position = options
+                    .next()
+                    .and_then(|value| value.parse::<f32>().ok())
+                    .expect("Invalid position, expected a number between -1 and 1");
+                pan.set_position(position);
+            }
+            "--auto" => auto_pan = Some(parse_timing(options.next(), "Hz", "auto-pan rate")),
+            "--depth" => {
+                let depth = options
+                    .next()
+                    .and_then(|value| value.parse::<f32>().ok())
+                    .expect("Invalid depth, expected a number between 0 and 1");
+                pan.set_depth(depth);
+            }
+            "--waveform" => pan.set_waveform(parse_waveform(options.next())),
+            _ => common.parse(arg, &mut options),
+        }
+    }
+
+    if !common.is_valid() {
+        print_usage(&args[0]);
+    }
+    common.resolve_bpm();
+    pan.set_auto_pan(auto_pan.map(|rate: Timing| rate.to_hz(common.bpm)));
+    process::run(&mut pan, &common);
+}
+
+// The target rate is the shared --target-rate option, so --rate stays free for raw input
+fn run_resample(args: &[String]) {
+    let mut quality = ResamplerQuality::default();
+    let mut common = CommonOptions::default();
+
+    let mut options = args[2..].iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--quality" => quality = parse_quality(options.next()),
+            _ => common.parse(arg, &mut options),
+        }
+    }
+
+    let Some(rate) = common.target_rate.filter(|_| common.is_valid()) else {
+        print_usage(&args[0]);
     };
-    let mut writer = hound::WavWriter::create(output_file, spec).unwrap();
-
-     // Initialize multiple delay line buffers with variable lengths
-     let mut delay_lengths = vec![
-        (SAMPLE_RATE as f64 * (DELAY_TIME_MS / 1000.0)) as usize,
-        (SAMPLE_RATE as f64 * (DELAY_TIME_MS / 1200.0)) as usize,
-        (SAMPLE_RATE as f64 * (DELAY_TIME_MS / 1400.0)) as usize,
-    ];
-    let mut delay_lines: Vec<Vec<f32>> = delay_lengths
-        .iter()
-        .map(|&length| vec![0.0; length * num_channels])
-        .collect();
-
-    // Process samples and apply reverb
-    let mut sample_counter = 0;
-    for (i, sample) in samples.iter().enumerate() {
-        let input_sample = *sample as f32;
-        let channel = i % num_channels;
-
-        // Update delay lengths periodically
-        if sample_counter % (SAMPLE_RATE * (num_channels as u32)) == 0 {
-            // You can use user input, an algorithm, or any other method to update delay_lengths
-            // For demonstration purposes, we simply increase each delay length by 100 samples
-            for (j, delay_length) in delay_lengths.iter_mut().enumerate() {
-                *delay_length += 100;
-
-                // Update delay_lines with the new delay lengths
-                let new_length = *delay_length * num_channels;
-                delay_lines[j].resize(new_length, 0.0);
+    // The dry signal would be mixed in at the wrong rate
+    if common.mix.is_some() {
+        eprintln!("--mix cannot be used with resample, the output has a different sample rate");
+        std::process::exit(1);
+    }
+    let mut resample = ResampleEffect::new(rate);
+    resample.set_quality(quality);
+    process::run(&mut resample, &common);
+}
+
+fn run_vocoder(args: &[String]) {
+    let mut vocoder = VocoderEffect::new(Carrier::Saw(DEFAULT_CARRIER_HZ));
+    let mut common = CommonOptions::default();
+
+    let mut options = args[2..].iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--carrier" => {
+                let carrier_file = options.next().expect("Missing carrier file after --carrier");
+                let carrier = audio_io::read_audio_file(carrier_file).expect("Failed to open carrier file");
+                vocoder.set_carrier(Carrier::Audio(carrier));
+            }
+            "--saw" => {
+                let frequency = options
+                    .next()
+                    .and_then(|value| value.trim_end_matches("Hz").parse::<f32>().ok())
+                    .filter(|&frequency| frequency > 0.0)
+                    .expect("Invalid carrier frequency, expected a value in Hz");
+                vocoder.set_carrier(Carrier::Saw(frequency));
+            }
+            "--noise" => vocoder.set_carrier(Carrier::Noise),
+            "--bands" => {
+                let num_bands = options
+                    .next()
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .expect("Invalid number of bands, expected 8 to 32");
+                vocoder.set_num_bands(num_bands);
             }
+            _ => common.parse(arg, &mut options),
         }
+    }
 
-        // Process each delay line
-        let mut wet_sample = 0.0;
-        for (delay_line, &_delay_length) in delay_lines.iter_mut().zip(delay_lengths.iter()) {
-            let delayed_sample = delay_line[channel];
+    if !common.is_valid() {
+        print_usage(&args[0]);
+    }
+    process::run(&mut vocoder, &common);
+}
 
-            // Combine input and delayed samples
-            wet_sample += input_sample * (1.0 - WET_DRY_MIX) + delayed_sample * WET_DRY_MIX;
+fn run_granular(args: &[String]) {
+    let mut granular = GranularEffect::new();
+    let (mut grain_size, mut spread) = (None, None);
+    let mut common = CommonOptions::default();
 
-            // Update delay line with feedback
-            let delay_input = input_sample + delayed_sample * FEEDBACK;
-            delay_line[channel] = delay_input;
+    let mut options = args[2..].iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--grain" => grain_size = Some(parse_timing(options.next(), "ms", "grain size")),
+            "--density" => granular.set_density(parse_number(options.next(), "density")),
+            "--spread" => spread = Some(parse_timing(options.next(), "ms", "position spread")),
+            "--pitch" => granular.set_pitch(parse_number(options.next(), "pitch")),
+            "--pitch-spread" => granular.set_pitch_spread(parse_number(options.next(), "pitch spread")),
+            "--reverse" => granular.set_reverse_probability(parse_number(options.next(), "reverse probability")),
+            "--wet" => granular.set_mix(parse_number(options.next(), "wet mix")),
+            "--seed" => granular.set_seed(parse_number(options.next(), "seed")),
+            _ => common.parse(arg, &mut options),
+        }
+    }
 
-            // Shift delay line
-            delay_line.rotate_right(num_channels);
+    if !common.is_valid() {
+        print_usage(&args[0]);
+    }
+    common.resolve_bpm();
+    if let Some(grain_size) = grain_size {
+        granular.set_grain_size_ms(Timing::to_ms(grain_size, common.bpm));
+    }
+    if let Some(spread) = spread {
+        granular.set_position_spread_ms(Timing::to_ms(spread, common.bpm));
+    }
+    process::run(&mut granular, &common);
+}
+
+fn run_tape(args: &[String]) {
+    let mut tape = TapeEffect::new();
+    let mut common = CommonOptions::default();
+
+    let mut options = args[2..].iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--drive" => tape.set_drive_db(parse_db(options.next())),
+            "--rolloff" => tape.set_rolloff_hz(parse_number(options.next(), "rolloff frequency")),
+            "--wow" => tape.set_wow(parse_number(options.next(), "wow depth")),
+            "--flutter" => tape.set_flutter(parse_number(options.next(), "flutter depth")),
+            _ => common.parse(arg, &mut options),
+        }
+    }
+
+    if !common.is_valid() {
+        print_usage(&args[0]);
+    }
+    process::run(&mut tape, &common);
+}
+
+fn run_pitch_shift(args: &[String]) {
+    let mut pitch_shift = PitchShiftEffect::new();
+    let mut common = CommonOptions::default();
+
+    let mut options = args[2..].iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--pitch" => pitch_shift.set_pitch(parse_number(options.next(), "pitch ratio")),
+            "--semitones" => pitch_shift.set_semitones(parse_number(options.next(), "transposition")),
+            "--quality" => pitch_shift.set_quality(parse_quality(options.next())),
+            _ => common.parse(arg, &mut options),
         }
+    }
+
+    if !common.is_valid() {
+        print_usage(&args[0]);
+    }
+    process::run(&mut pitch_shift, &common);
+}
 
-        wet_sample /= NUM_DELAY_LINES as f32; // Normalize the wet_sample
-        let output_sample = wet_sample as i16;
-        writer.write_sample(output_sample).unwrap();
+fn run_time_stretch(args: &[String]) {
+    let mut time_stretch = TimeStretchEffect::new();
+    let mut stretch = None;
+    let mut common = CommonOptions::default();
 
-        sample_counter += 1;
+    let mut options = args[2..].iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--stretch" => stretch = Some(parse_number(options.next(), "stretch factor")),
+            _ => common.parse(arg, &mut options),
+        }
+    }
+
+    let Some(stretch) = stretch.filter(|_| common.is_valid()) else {
+        print_usage(&args[0]);
+    };
+    // The dry signal would no longer line up with the stretched one
+    if common.mix.is_some() {
+        eprintln!("--mix cannot be used with time-stretch, the output has a different length");
+        std::process::exit(1);
     }
+    time_stretch.set_stretch(stretch);
+    process::run(&mut time_stretch, &common);
+}
 
-    writer.finalize().unwrap();
-    println!("Reverb effect applied. Check the output file: {}", output_file);
-}
\ No newline at end of file
+fn main() {
+    // Parse command line arguments for the output WAV file path:
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("analyze") => analysis::run(&args),
+        Some("split") => split::run(&args),
+        Some("stats") => stats::run(&args),
+        Some("diff") => diff::run(&args),
+        Some("gain") => run_gain(&args),
+        Some("stereo-width") => run_stereo_width(&args),
+        Some("pan") => run_pan(&args),
+        Some("resample") => run_resample(&args),
+        Some("vocoder") => run_vocoder(&args),
+        Some("granular") => run_granular(&args),
+        Some("tape") => run_tape(&args),
+        Some("pitch-shift") => run_pitch_shift(&args),
+        Some("time-stretch") => run_time_stretch(&args),
+        _ => run_reverb(&args),
+    }
+}
@@ -0,0 +1,544 @@
+/*
+The pipeline shared by the effect commands. Each command parses its own options and hands
+everything it does not recognize to `CommonOptions::parse`, so the shared flags work the same
+way everywhere. The input is read (through ffmpeg with --via-ffmpeg) and its non-finite samples
+replaced with silence, then the effect runs on it. --bpm auto takes the tempo for note values
+from the input itself, with the same estimator as analyze --bpm.
+
+Rendering lines the output up with the input. With --tail-seconds the input is extended with
+silence for up to that long (or the effect's own tail, if shorter), so reverbs and delays can
+decay instead of stopping with the input, and the silence left after the decay is trimmed
+again. Effects that report latency get that many extra frames of silence, and the same number
+of frames is dropped from the start of their output. NaN or infinite output is replaced with
+silence and reported.
+
+The output can then be matched in level to the input and converted to another sample rate
+before it is written, and clipping is reported. With --report the level statistics of the input
+and the output are measured as well, for the caller to print next to the clipping report.
+
+Either file of a pair can be `-` for standard input or output, so the tool can sit in a
+pipeline; the output WAV is built in memory so its header holds the real length, and all
+messages go to stderr while the audio goes to stdout. With --out-dir the files are inputs (or
+glob patterns) for a batch run instead of an input/output pair.
+ */
+use std::fs;
+use std::io::{self, Cursor, Read, Write};
+use std::path::PathBuf;
+
+use audiofxrs::audio_io::{self, AudioData};
+use audiofxrs::dsp::sanitize::{is_finite, sanitize};
+use audiofxrs::effects::{ParallelMix, ResampleEffect};
+use audiofxrs::AudioEffect;
+
+use crate::analysis;
+use crate::batch;
+use crate::ffmpeg;
+use crate::stats::FileStats;
+
+pub const COMMON_USAGE: &str = "[--bpm <tempo> | auto] [--mix <0-1>] [--match-level] [--tail-seconds <s>] [--target-rate <Hz>] [--report] [--via-ffmpeg] \
+     [--raw --rate <Hz> [--channels <n>]] [--out-dir <dir> [--jobs <n>]]";
+
+/// The file name that stands for standard input or output.
+pub const STDIO: &str = "-";
+
+const TAIL_SILENCE_THRESHOLD: f32 = 1e-6; // -120 dBFS
+
+#[derive(Debug)]
+pub struct CommonOptions {
+    /// Tempo for note values such as 1/8 in time and rate options.
+    pub bpm: Option<f32>,
+    /// Estimate the tempo from the input file, with --bpm auto.
+    pub auto_bpm: bool,
+    pub mix: Option<f32>,
+    pub match_level: bool,
+    pub tail_secs: Option<f32>,
+    pub target_rate: Option<u32>,
+    pub report: bool,
+    pub via_ffmpeg: bool,
+    /// Sample rate and channel count of raw PCM input, with --raw.
+    pub raw: Option<(Option<u32>, usize)>,
+    pub out_dir: Option<PathBuf>,
+    pub jobs: usize,
+    pub files: Vec<String>,
+    pub unknown_flag: bool,
+}
+
+impl Default for CommonOptions {
+    fn default() -> Self {
+        CommonOptions {
+            bpm: None,
+            auto_bpm: false,
+            mix: None,
+            match_level: false,
+            tail_secs: None,
+            target_rate: None,
+            report: false,
+            via_ffmpeg: false,
+            raw: None,
+            out_dir: None,
+            jobs: 1,
+            files: Vec::new(),
+            unknown_flag: false,
+        }
+    }
+}
+
+impl CommonOptions {
+    /// Handles an argument that is not specific to the command, taking its value from `options`.
+    pub fn parse<'a>(&mut self, arg: &str, options: &mut impl Iterator<Item = &'a String>) {
+        match arg {
+            "--bpm" => match options.next().map(String::as_str) {
+                Some("auto") => self.auto_bpm = true,
+                value => {
+                    let bpm = value
+                        .and_then(|value| value.parse::<f32>().ok())
+                        .filter(|&bpm| bpm > 0.0)
+                        .expect("Invalid tempo, expected beats per minute or auto");
+                    self.bpm = Some(bpm);
+                }
+            },
+            "--mix" => {
+                let mix = options
+                    .next()
+                    .and_then(|value| value.parse::<f32>().ok())
+                    .expect("Invalid mix, expected a number between 0 and 1");
+                self.mix = Some(mix);
+            }
+            "--match-level" => self.match_level = true,
+            "--target-rate" => {
+                let rate = options
+                    .next()
+                    .and_then(|value| value.trim_end_matches("Hz").parse::<u32>().ok())
+                    .filter(|&rate| rate > 0)
+                    .expect("Invalid target rate, expected a sample rate in Hz");
+                self.target_rate = Some(rate);
+            }
+            "--tail-seconds" => {
+                let tail_secs = options
+                    .next()
+                    .and_then(|value| value.trim_end_matches('s').parse::<f32>().ok())
+                    .filter(|&secs| secs >= 0.0)
+                    .expect("Invalid tail length, expected a duration in seconds");
+                self.tail_secs = Some(tail_secs);
+            }
+            "--report" => self.report = true,
+            "--via-ffmpeg" => self.via_ffmpeg = true,
+            "--raw" => self.raw = Some(self.raw.unwrap_or((None, 1))),
+            "--rate" => {
+                let rate = options
+                    .next()
+                    .and_then(|value| value.trim_end_matches("Hz").parse::<u32>().ok())
+                    .filter(|&rate| rate > 0)
+                    .expect("Invalid raw sample rate, expected a value in Hz");
+                let channels = self.raw.map_or(1, |(_, channels)| channels);
+                self.raw = Some((Some(rate), channels));
+            }
+            "--channels" => {
+                let channels = options
+                    .next()
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .filter(|&channels| channels > 0)
+                    .expect("Invalid channel count, expected a positive integer");
+                let rate = self.raw.and_then(|(rate, _)| rate);
+                self.raw = Some((rate, channels));
+            }
+            "--out-dir" => {
+                let out_dir = options.next().expect("Missing output directory after --out-dir");
+                self.out_dir = Some(PathBuf::from(out_dir));
+            }
+            "--jobs" => {
+                self.jobs = options
+                    .next()
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .filter(|&jobs| jobs > 0)
+                    .expect("Invalid number of jobs, expected a positive integer");
+            }
+            _ if arg.starts_with("--") => self.unknown_flag = true,
+            _ => self.files.push(arg.to_string()),
+        }
+    }
+
+    /// True when the files fit the mode (an input and an output, or at least one input for a
+    /// batch), raw input has a sample rate and every flag was recognized.
+    pub fn is_valid(&self) -> bool {
+        let files_ok = match self.out_dir {
+            Some(_) => !self.files.is_empty(),
+            None => self.files.len() == 2,
+        };
+        let raw_ok = self.raw.is_none_or(|(rate, _)| rate.is_some());
+        files_ok && raw_ok && !self.unknown_flag
+    }
+
+    /// Sample rate and channel count to read raw PCM input with, once --raw has its rate.
+    pub fn raw_input(&self) -> Option<(u32, usize)> {
+        self.raw.and_then(|(rate, channels)| Some((rate?, channels)))
+    }
+
+    /// With --bpm auto, estimates the tempo of the input file and uses it for note values.
+    /// Commands call this once the arguments are parsed, before resolving any `Timing`.
+    pub fn resolve_bpm(&mut self) {
+        if !self.auto_bpm {
+            return;
+        }
+        let input_file = match self.files.first() {
+            Some(file) if self.out_dir.is_none() && file != STDIO && !self.via_ffmpeg => file,
+            _ => {
+                eprintln!("--bpm auto needs a single input file (not stdin, --via-ffmpeg or --out-dir)");
+                std::process::exit(1);
+            }
+        };
+        let input = read_or_exit(input_file, self.raw_input());
+        match analysis::estimate_bpm(&analysis::downmix(&input), input.sample_rate) {
+            Some(bpm) => {
+                let message = format!("Detected tempo: {:.1} BPM", bpm);
+                if self.writes_stdout() {
+                    eprintln!("{}", message);
+                } else {
+                    println!("{}", message);
+                }
+                self.bpm = Some(bpm);
+            }
+            None => {
+                eprintln!("Could not estimate the tempo of {}; give it with --bpm <tempo>", input_file);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// True when the audio output goes to stdout, so messages must not.
+    pub fn writes_stdout(&self) -> bool {
+        self.out_dir.is_none() && self.files.get(1).is_some_and(|file| file == STDIO)
+    }
+}
+
+/// What happened to one file, for the caller to print.
+#[derive(Debug, Default)]
+pub struct FileReport {
+    /// Warnings raised while processing, printed with the rest of the report.
+    pub warnings: Vec<String>,
+    /// Non-finite samples the effect produced, replaced with silence.
+    pub replaced_output_samples: usize,
+    pub level_match_db: Option<f32>,
+    pub clipped_samples: usize,
+    pub max_overshoot_db: f32,
+    /// Input and output statistics, with --report.
+    pub stats: Option<(FileStats, FileStats)>,
+}
+
+impl FileReport {
+    /// Prints the report; informational lines go to stderr instead of stdout with `to_stderr`.
+    pub fn print(&self, effect_name: &str, to_stderr: bool) {
+        for warning in &self.warnings {
+            eprintln!("{}", warning);
+        }
+        let mut info = String::new();
+        if let Some(gain_db) = self.level_match_db {
+            info += &format!("Level matched: applied {:+.2} dB\n", gain_db);
+        }
+        if self.clipped_samples > 0 {
+            eprintln!(
+                "Warning: {} clipped {} sample(s), max overshoot {:.2} dB",
+                effect_name, self.clipped_samples, self.max_overshoot_db
+            );
+        }
+        if let Some((input_stats, output_stats)) = &self.stats {
+            info += &input_stats.table("Input");
+            info += &output_stats.table("Output");
+        }
+        if to_stderr {
+            eprint!("{}", info);
+        } else {
+            print!("{}", info);
+        }
+    }
+}
+
+fn rms(values: impl Iterator<Item = f32>) -> f64 {
+    let (sum, count) = values.fold((0.0_f64, 0_usize), |(sum, count), v| (sum + (v as f64).powi(2), count + 1));
+    (sum / count.max(1) as f64).sqrt()
+}
+
+// Cuts the silence after the last audible frame, but never shortens the output below `min_frames`
+fn trim_tail(audio: &mut AudioData, min_frames: usize) {
+    let last_audible = audio
+        .samples
+        .iter()
+        .rposition(|sample| sample.abs() > TAIL_SILENCE_THRESHOLD)
+        .map_or(0, |index| index / audio.num_channels + 1);
+    let num_frames = last_audible.max(min_frames).min(audio.num_frames());
+    audio.samples.truncate(num_frames * audio.num_channels);
+}
+
+/// Reads `input_file` (or stdin for `-`), replacing NaN and infinite samples with silence. With
+/// `raw`, the bytes are taken as headerless 16-bit PCM at that sample rate and channel count.
+/// Warnings are collected in `warnings` rather than printed, so batch workers do not interleave
+/// them.
+pub fn read_sanitized(input_file: &str, raw: Option<(u32, usize)>, warnings: &mut Vec<String>) -> Result<AudioData, String> {
+    let read_error = |e: &dyn std::fmt::Display| format!("Failed to read {}: {}", input_file, e);
+    let mut bytes = Vec::new();
+    if input_file == STDIO {
+        io::stdin().read_to_end(&mut bytes).map_err(|e| read_error(&e))?;
+    } else {
+        bytes = fs::read(input_file).map_err(|e| read_error(&e))?;
+    }
+
+    let mut input = match raw {
+        Some((rate, channels)) => audio_io::read_raw_pcm(&bytes, rate, channels),
+        None => audio_io::read_audio_bytes(bytes).map_err(|e| read_error(&e))?,
+    };
+    let replaced = sanitize(&mut input.samples);
+    if replaced > 0 {
+        warnings.push(format!("Warning: replaced {} NaN/infinite sample(s) in {} with silence", replaced, input_file));
+    }
+    Ok(input)
+}
+
+/// Reads `input_file` with `read_sanitized`, printing its warnings, or exits if it cannot be read.
+pub fn read_or_exit(input_file: &str, raw: Option<(u32, usize)>) -> AudioData {
+    let mut warnings = Vec::new();
+    let input = read_sanitized(input_file, raw, &mut warnings).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    });
+    warnings.iter().for_each(|warning| eprintln!("{}", warning));
+    input
+}
+
+/// Runs `effect` on `input` with the tail, latency compensation and dry/wet mix asked for.
+fn render<E: AudioEffect>(
+    effect: &mut E,
+    input: &AudioData,
+    options: &CommonOptions,
+    report: &mut FileReport,
+) -> AudioData {
+    // --tail-seconds feeds silence after the input so the effect's decay is rendered, and
+    // latency compensation needs as much again to push the end of the input through
+    let tail_frames = options
+        .tail_secs
+        .map_or(0, |limit| (limit.min(effect.tail_secs()) * input.sample_rate as f32) as usize);
+    let latency = effect.latency_samples();
+    let padded;
+    let effect_input = if tail_frames + latency > 0 {
+        let mut samples = input.samples.clone();
+        samples.resize(samples.len() + (tail_frames + latency) * input.num_channels, 0.0);
+        padded = AudioData::new(samples, input.sample_rate, input.num_channels);
+        &padded
+    } else {
+        input
+    };
+
+    // --mix blends the effect with the dry input, for parallel processing. Both are at the input
+    // rate: --target-rate only converts the mixed result below
+    let mut output = match options.mix {
+        Some(mix) => ParallelMix::new(&mut *effect, mix).process(effect_input),
+        None => effect.process(effect_input),
+    };
+    // An effect driven past its limits can blow up; silence the damage instead of writing it
+    if !is_finite(&output.samples) {
+        let replaced = sanitize(&mut output.samples);
+        report.replaced_output_samples = replaced;
+        report.warnings.push(format!(
+            "Warning: {} produced {} NaN/infinite sample(s), replaced with silence",
+            effect.name(),
+            replaced
+        ));
+    }
+    if latency > 0 {
+        let latency = (latency * output.num_channels).min(output.samples.len());
+        output.samples.drain(..latency);
+        // The padding (and with --mix, the delayed dry copy) runs past the end; keep only the
+        // input and its tail
+        let frames = ((input.num_frames() + tail_frames) as u64 * output.sample_rate as u64)
+            .div_ceil(input.sample_rate as u64) as usize;
+        output.samples.truncate(frames * output.num_channels);
+    }
+    if tail_frames > 0 {
+        trim_tail(&mut output, input.num_frames());
+    }
+    output
+}
+
+/// Runs `effect` on `input_file` and writes `output_file`.
+pub fn process_file<E: AudioEffect>(
+    effect: &mut E,
+    input_file: &str,
+    output_file: &str,
+    options: &CommonOptions,
+) -> Result<FileReport, String> {
+    let mut report = FileReport::default();
+    if options.via_ffmpeg && (input_file == STDIO || output_file == STDIO) {
+        return Err("--via-ffmpeg cannot read from stdin or write to stdout".to_string());
+    }
+
+    // With --via-ffmpeg, the effect reads and writes temporary WAV files that ffmpeg converts
+    let (input_wav, output_wav) = if options.via_ffmpeg {
+        (ffmpeg::decode(input_file)?, ffmpeg::output_wav())
+    } else {
+        (PathBuf::from(input_file), PathBuf::from(output_file))
+    };
+
+    let input = read_sanitized(&input_wav.to_string_lossy(), options.raw_input(), &mut report.warnings);
+    if options.via_ffmpeg {
+        let _ = std::fs::remove_file(&input_wav);
+    }
+    let input = input?;
+
+    let mut output = render(effect, &input, options, &mut report);
+
+    // --match-level scales the output to the input's RMS level, so A/B comparisons are not
+    // biased by the loudness the effect adds or removes
+    if options.match_level {
+        let input_rms = rms(input.samples.iter().copied());
+        let output_rms = rms(output.samples.iter().copied());
+        if input_rms > 0.0 && output_rms > 0.0 {
+            let gain = (input_rms / output_rms) as f32;
+            output.samples.iter_mut().for_each(|x| *x *= gain);
+            report.level_match_db = Some(20.0 * gain.log10());
+        }
+    }
+
+    // --target-rate converts the result, whatever rate the input had
+    if let Some(rate) = options.target_rate {
+        output = ResampleEffect::new(rate).process(&output);
+    }
+
+    // Writing saturates at the 16-bit range, so count the samples that will be clipped
+    let mut max_overshoot = 0.0_f32;
+    for overshoot in output.samples.iter().filter_map(|&sample| audio_io::i16_overshoot(sample)) {
+        report.clipped_samples += 1;
+        max_overshoot = max_overshoot.max(overshoot);
+    }
+    report.max_overshoot_db = 20.0 * max_overshoot.log10();
+
+    if options.report {
+        report.stats = Some((FileStats::measure(&input), FileStats::measure(&output)));
+    }
+
+    let written = if output_file == STDIO {
+        let mut buffer = Cursor::new(Vec::new());
+        audio_io::write_wav_to(&mut buffer, &output).and_then(|()| Ok(io::stdout().write_all(buffer.get_ref())?))
+    } else {
+        audio_io::write_wav(&output_wav, &output)
+    };
+    written.map_err(|e| format!("Failed to write {}: {}", output_wav.display(), e))?;
+    if options.via_ffmpeg {
+        ffmpeg::encode(&output_wav, output_file)?;
+    }
+
+    Ok(report)
+}
+
+/// Runs `effect` on the files given in `options`, as a single file or as a batch.
+pub fn run<E: AudioEffect + Clone + Send>(effect: &mut E, options: &CommonOptions) {
+    if let Some(out_dir) = &options.out_dir {
+        batch::run(effect, out_dir, options);
+        return;
+    }
+
+    let input_file = &options.files[0];
+    let output_file = &options.files[1];
+    let to_stdout = options.writes_stdout();
+    match process_file(effect, input_file, output_file, options) {
+        Ok(report) => report.print(effect.name(), to_stdout),
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
+
+    if to_stdout {
+        return;
+    }
+    let mut name = effect.name().to_string();
+    if let Some(first) = name.get_mut(0..1) {
+        first.make_ascii_uppercase();
+    }
+    println!("{} effect applied. Check the output file: {}", name, output_file);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Passes the input through `latency` frames late, as a lookahead effect would
+    #[derive(Clone)]
+    struct Lookahead {
+        latency: usize,
+    }
+
+    impl AudioEffect for Lookahead {
+        fn name(&self) -> &str {
+            "lookahead"
+        }
+
+        fn process(&mut self, input: &AudioData) -> AudioData {
+            let mut samples = vec![0.0; self.latency * input.num_channels];
+            samples.extend_from_slice(&input.samples);
+            AudioData::new(samples, input.sample_rate, input.num_channels)
+        }
+
+        fn latency_samples(&self) -> usize {
+            self.latency
+        }
+    }
+
+    // Blows up halfway through, like an unstable recursive filter
+    #[derive(Clone)]
+    struct Unstable;
+
+    impl AudioEffect for Unstable {
+        fn name(&self) -> &str {
+            "unstable"
+        }
+
+        fn process(&mut self, input: &AudioData) -> AudioData {
+            let mut output = input.clone();
+            let half = output.samples.len() / 2;
+            output.samples[half..].fill(f32::NAN);
+            output
+        }
+    }
+
+    fn ramp() -> AudioData {
+        let samples = (0..200).map(|i| (i as f32 + 1.0) / 200.0).collect();
+        AudioData::new(samples, 48000, 2)
+    }
+
+    #[test]
+    fn latency_is_compensated() {
+        let input = ramp();
+        let output = render(
+            &mut Lookahead { latency: 37 },
+            &input,
+            &CommonOptions::default(),
+            &mut FileReport::default(),
+        );
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn latency_is_compensated_with_mix() {
+        // Dry and wet line up, so any blend of them is the input again, with nothing appended
+        let input = ramp();
+        let options = CommonOptions {
+            mix: Some(0.5),
+            ..CommonOptions::default()
+        };
+        let output = render(&mut Lookahead { latency: 37 }, &input, &options, &mut FileReport::default());
+        assert_eq!(output.num_frames(), input.num_frames());
+        for (actual, expected) in output.samples.iter().zip(&input.samples) {
+            assert!((actual - expected).abs() < 1e-6, "expected {}, got {}", expected, actual);
+        }
+    }
+
+    #[test]
+    fn non_finite_output_is_silenced_and_reported() {
+        let input = ramp();
+        let mut report = FileReport::default();
+        let output = render(&mut Unstable, &input, &CommonOptions::default(), &mut report);
+        assert_eq!(&output.samples[..100], &input.samples[..100]);
+        assert!(output.samples[100..].iter().all(|&sample| sample == 0.0));
+        assert_eq!(report.replaced_output_samples, 100);
+        assert_eq!(report.warnings.len(), 1);
+    }
+}
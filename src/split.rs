@@ -0,0 +1,206 @@
+/*
+Splitting cuts a long recording into separate takes at its silences.
+The input is measured in short blocks, and a block counts as silent when its RMS level (over all
+channels) is below the threshold. Whenever silent blocks last at least the minimum silence
+duration, the region before them is closed and written as its own numbered file. The input can
+be any format `audio_io` reads, or standard input with `-`; the takes are written as 16-bit WAV
+like the effect commands' output.
+ */
+use audiofxrs::audio_io::{self, AudioData};
+
+use crate::process;
+
+const BLOCK_SIZE_MS: u32 = 10;
+const DEFAULT_THRESHOLD_DB: f32 = -50.0;
+const DEFAULT_MIN_SILENCE_SECS: f32 = 0.5;
+
+pub fn run(args: &[String]) {
+    let (threshold_db, min_silence_secs, positional) = parse_args(&args[2..]).unwrap_or_else(|message| fail(message));
+    if positional.len() != 2 {
+        eprintln!(
+            "Usage: {} split <input.wav | -> <output_prefix> [--threshold <dB>] [--min-silence <seconds>]",
+            args[0]
+        );
+        std::process::exit(1);
+    }
+    let input_file = positional[0];
+    let output_prefix = positional[1];
+
+    let audio = process::read_or_exit(input_file, None);
+
+    let regions = find_regions(&audio, threshold_db, min_silence_secs);
+    write_regions(&audio, &regions, output_prefix);
+    println!("Split {} into {} take(s)", input_file, regions.len());
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+/// Parses the split options, returning the threshold in dB, the minimum silence in seconds and
+/// the positional arguments.
+fn parse_args(args: &[String]) -> Result<(f32, f32, Vec<&String>), &'static str> {
+    let mut threshold_db = DEFAULT_THRESHOLD_DB;
+    let mut min_silence_secs = DEFAULT_MIN_SILENCE_SECS;
+    let mut positional = Vec::new();
+
+    let mut options = args.iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--threshold" => {
+                threshold_db = options
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .ok_or("Invalid threshold, expected a level in dB")?;
+            }
+            "--min-silence" => {
+                min_silence_secs = options
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .filter(|&secs: &f32| secs >= 0.0)
+                    .ok_or("Invalid minimum silence, expected a duration in seconds")?;
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    Ok((threshold_db, min_silence_secs, positional))
+}
+
+/// Returns the non-silent regions of `audio` as `(start, end)` frame ranges.
+fn find_regions(audio: &AudioData, threshold_db: f32, min_silence_secs: f32) -> Vec<(usize, usize)> {
+    let num_channels = audio.num_channels;
+    let block_frames = (audio.sample_rate * BLOCK_SIZE_MS / 1000).max(1) as usize;
+    let min_silent_blocks = ((min_silence_secs * 1000.0) / BLOCK_SIZE_MS as f32).ceil().max(1.0) as usize;
+    let threshold = 10.0_f32.powf(threshold_db / 20.0);
+
+    let mut regions = Vec::new();
+    let mut region_start = None;
+    let mut last_loud_block = 0;
+    let mut silent_blocks = 0;
+
+    for (block, chunk) in audio.samples.chunks(block_frames * num_channels).enumerate() {
+        let rms = (chunk.iter().map(|s| s.powi(2)).sum::<f32>() / chunk.len() as f32).sqrt();
+
+        if rms >= threshold {
+            region_start.get_or_insert(block);
+            last_loud_block = block;
+            silent_blocks = 0;
+        } else {
+            silent_blocks += 1;
+            if silent_blocks >= min_silent_blocks {
+                if let Some(start) = region_start.take() {
+                    regions.push((start, last_loud_block + 1));
+                }
+            }
+        }
+    }
+    if let Some(start) = region_start {
+        regions.push((start, last_loud_block + 1));
+    }
+
+    let num_frames = audio.num_frames();
+    regions
+        .into_iter()
+        .map(|(start, end)| (start * block_frames, (end * block_frames).min(num_frames)))
+        .collect()
+}
+
+/// Writes every region to `<output_prefix>_<nnn>.wav`, numbered from 001.
+fn write_regions(audio: &AudioData, regions: &[(usize, usize)], output_prefix: &str) {
+    let num_channels = audio.num_channels;
+    let sample_rate = audio.sample_rate as f32;
+
+    for (i, &(start, end)) in regions.iter().enumerate() {
+        let output_file = format!("{}_{:03}.wav", output_prefix, i + 1);
+        let samples = audio.samples[start * num_channels..end * num_channels].to_vec();
+        let take = AudioData::new(samples, audio.sample_rate, num_channels);
+        if let Err(e) = audio_io::write_wav(&output_file, &take) {
+            fail(&format!("Failed to write {}: {}", output_file, e));
+        }
+
+        println!("{}: {:.2}s - {:.2}s", output_file, start as f32 / sample_rate, end as f32 / sample_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 1000;
+
+    // Loud (0.5) and silent stretches of the given lengths in seconds, alternating from loud
+    fn bursts(num_channels: usize, lengths: &[f32]) -> AudioData {
+        let mut samples = Vec::new();
+        for (index, &secs) in lengths.iter().enumerate() {
+            let level = if index % 2 == 0 { 0.5 } else { 0.0 };
+            samples.extend(std::iter::repeat_n(level, (secs * RATE as f32) as usize * num_channels));
+        }
+        AudioData::new(samples, RATE, num_channels)
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn takes_are_cut_at_long_silences() {
+        let audio = bursts(1, &[1.0, 1.0, 0.5, 0.2, 0.5]);
+        // The 0.2 s gap is too short to end a take
+        assert_eq!(find_regions(&audio, -50.0, 0.5), vec![(0, 1000), (2000, 3200)]);
+        assert_eq!(find_regions(&audio, -50.0, 0.1), vec![(0, 1000), (2000, 2500), (2700, 3200)]);
+    }
+
+    #[test]
+    fn leading_and_trailing_silence_is_dropped() {
+        let audio = bursts(2, &[0.0, 0.3, 0.5, 0.7]);
+        assert_eq!(find_regions(&audio, -50.0, 0.5), vec![(300, 800)]);
+    }
+
+    #[test]
+    fn threshold_decides_what_is_silent() {
+        let audio = bursts(1, &[1.0]);
+        // 0.5 is about -6 dBFS
+        assert_eq!(find_regions(&audio, -7.0, 0.5), vec![(0, 1000)]);
+        assert!(find_regions(&audio, -5.0, 0.5).is_empty());
+    }
+
+    #[test]
+    fn silent_input_has_no_takes() {
+        let audio = AudioData::new(vec![0.0; 2000], RATE, 2);
+        assert!(find_regions(&audio, -50.0, 0.5).is_empty());
+        assert!(find_regions(&AudioData::new(Vec::new(), RATE, 1), -50.0, 0.5).is_empty());
+    }
+
+    #[test]
+    fn single_sample_is_one_take() {
+        let audio = AudioData::new(vec![0.5], RATE, 1);
+        assert_eq!(find_regions(&audio, -50.0, 0.5), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn one_loud_channel_keeps_the_block() {
+        let samples = (0..1000).flat_map(|_| [0.0, 0.5]).collect();
+        let audio = AudioData::new(samples, RATE, 2);
+        assert_eq!(find_regions(&audio, -50.0, 0.5), vec![(0, 1000)]);
+    }
+
+    #[test]
+    fn options_are_parsed_around_positional_arguments() {
+        let args = strings(&["in.wav", "--threshold", "-40", "take", "--min-silence", "1.5"]);
+        let (threshold_db, min_silence_secs, positional) = parse_args(&args).unwrap();
+        assert_eq!((threshold_db, min_silence_secs), (-40.0, 1.5));
+        assert_eq!(positional, vec!["in.wav", "take"]);
+
+        let (threshold_db, min_silence_secs, _) = parse_args(&strings(&["in.wav", "take"])).unwrap();
+        assert_eq!((threshold_db, min_silence_secs), (DEFAULT_THRESHOLD_DB, DEFAULT_MIN_SILENCE_SECS));
+    }
+
+    #[test]
+    fn invalid_options_are_refused() {
+        assert!(parse_args(&strings(&["--threshold", "loud"])).is_err());
+        assert!(parse_args(&strings(&["--threshold"])).is_err());
+        assert!(parse_args(&strings(&["--min-silence", "-1"])).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+/*
+Level statistics of a file: peak, RMS, crest factor, DC offset and clipped samples, for every
+channel and for all channels together.
+A sample counts as clipped when it lies beyond the 16-bit range, above 32767/32768 or below -1.0,
+the same definition the effect commands use for output that will saturate when written.
+With --report, the effect commands print these statistics for the input and the output.
+ */
+use audiofxrs::audio_io::{self, AudioData};
+use audiofxrs::dsp::envelope::linear_to_db;
+
+use crate::process;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LevelStats {
+    pub peak: f32,
+    pub rms: f32,
+    pub dc_offset: f32,
+    pub clipped_samples: usize,
+}
+
+impl LevelStats {
+    pub fn measure(samples: impl Iterator<Item = f32>) -> Self {
+        let mut stats = LevelStats::default();
+        let (mut sum, mut sum_squares, mut count) = (0.0_f64, 0.0_f64, 0_usize);
+        for sample in samples {
+            stats.peak = stats.peak.max(sample.abs());
+            if audio_io::i16_overshoot(sample).is_some() {
+                stats.clipped_samples += 1;
+            }
+            sum += sample as f64;
+            sum_squares += (sample as f64).powi(2);
+            count += 1;
+        }
+        let count = count.max(1) as f64;
+        stats.rms = (sum_squares / count).sqrt() as f32;
+        stats.dc_offset = (sum / count) as f32;
+        stats
+    }
+
+    /// Peak to RMS ratio in dB.
+    pub fn crest_factor_db(&self) -> f32 {
+        linear_to_db(self.peak) - linear_to_db(self.rms)
+    }
+}
+
+/// Statistics of a whole file, per channel and for all channels together.
+#[derive(Debug, Clone)]
+pub struct FileStats {
+    pub num_channels: usize,
+    pub sample_rate: u32,
+    pub duration_secs: f32,
+    /// One entry per channel, followed by one for all channels if there are several.
+    pub channels: Vec<LevelStats>,
+}
+
+impl FileStats {
+    pub fn measure(audio: &AudioData) -> Self {
+        let num_channels = audio.num_channels;
+        let mut channels: Vec<LevelStats> = (0..num_channels)
+            .map(|channel| LevelStats::measure(audio.samples.iter().skip(channel).step_by(num_channels).copied()))
+            .collect();
+        if num_channels > 1 {
+            channels.push(LevelStats::measure(audio.samples.iter().copied()));
+        }
+        FileStats {
+            num_channels,
+            sample_rate: audio.sample_rate,
+            duration_secs: audio.duration_secs(),
+            channels,
+        }
+    }
+
+    /// Formats the statistics as a table headed by `title`.
+    pub fn table(&self, title: &str) -> String {
+        let mut table = format!(
+            "{}: {} channel(s), {} Hz, {:.2} s\n",
+            title, self.num_channels, self.sample_rate, self.duration_secs
+        );
+        table += "  Channel  Peak dBFS  RMS dBFS  Crest dB  DC offset  Clipped\n";
+        for (index, stats) in self.channels.iter().enumerate() {
+            let label = if index < self.num_channels {
+                (index + 1).to_string()
+            } else {
+                "all".to_string()
+            };
+            table += &format!(
+                "  {:<7}  {:>9.2}  {:>8.2}  {:>8.2}  {:>+9.5}  {:>7}\n",
+                label,
+                linear_to_db(stats.peak),
+                linear_to_db(stats.rms),
+                stats.crest_factor_db(),
+                stats.dc_offset,
+                stats.clipped_samples
+            );
+        }
+        table
+    }
+}
+
+pub fn run(args: &[String]) {
+    let input_file = match &args[2..] {
+        [input_file] if !input_file.starts_with("--") => input_file,
+        _ => {
+            eprintln!("Usage: {} stats <input.wav | ->", args[0]);
+            std::process::exit(1);
+        }
+    };
+
+    let audio = process::read_or_exit(input_file, None);
+    print!("{}", FileStats::measure(&audio).table(input_file));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: f32, expected: f32) -> bool {
+        (actual - expected).abs() < 1e-5
+    }
+
+    #[test]
+    fn levels_of_a_known_signal() {
+        let stats = LevelStats::measure([0.5, -0.5, 0.5, -0.5, 1.0, 0.0].into_iter());
+        assert_eq!(stats.peak, 1.0);
+        assert!(close(stats.rms, (2.0_f32 / 6.0).sqrt()));
+        assert!(close(stats.dc_offset, 1.0 / 6.0));
+        assert_eq!(stats.clipped_samples, 1);
+    }
+
+    #[test]
+    fn sine_crest_factor_is_3_db() {
+        let sine = (0..4800).map(|n| (2.0 * std::f32::consts::PI * n as f32 / 48.0).sin());
+        let stats = LevelStats::measure(sine);
+        assert!((stats.crest_factor_db() - 3.01).abs() < 0.01);
+        assert!(stats.dc_offset.abs() < 1e-4);
+    }
+
+    #[test]
+    fn clipping_follows_the_16_bit_range() {
+        let stats = LevelStats::measure([32767.0 / 32768.0, -1.0, 1.0, -1.001].into_iter());
+        assert_eq!(stats.clipped_samples, 2);
+    }
+
+    #[test]
+    fn silence_and_empty_input_measure_zero() {
+        for samples in [vec![0.0; 16], Vec::new()] {
+            let stats = LevelStats::measure(samples.into_iter());
+            assert_eq!((stats.peak, stats.rms, stats.dc_offset, stats.clipped_samples), (0.0, 0.0, 0.0, 0));
+        }
+    }
+
+    #[test]
+    fn single_sample() {
+        let stats = LevelStats::measure(std::iter::once(-0.25));
+        assert_eq!((stats.peak, stats.rms, stats.dc_offset), (0.25, 0.25, -0.25));
+        assert!(close(stats.crest_factor_db(), 0.0));
+    }
+
+    #[test]
+    fn mono_has_no_combined_entry() {
+        let stats = FileStats::measure(&AudioData::new(vec![0.5; 480], 48000, 1));
+        assert_eq!(stats.channels.len(), 1);
+        assert!(close(stats.duration_secs, 0.01));
+        assert!(!stats.table("mono").contains("all"));
+    }
+
+    #[test]
+    fn stereo_is_measured_per_channel_and_together() {
+        let samples = (0..100).flat_map(|_| [0.5, -0.25]).collect();
+        let stats = FileStats::measure(&AudioData::new(samples, 48000, 2));
+        assert_eq!(stats.channels.len(), 3);
+        assert_eq!((stats.channels[0].peak, stats.channels[0].dc_offset), (0.5, 0.5));
+        assert_eq!((stats.channels[1].peak, stats.channels[1].dc_offset), (0.25, -0.25));
+        assert_eq!(stats.channels[2].peak, 0.5);
+        assert!(close(stats.channels[2].dc_offset, 0.125));
+        assert_eq!(stats.table("stereo").lines().count(), 5);
+    }
+}
+
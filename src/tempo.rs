@@ -0,0 +1,63 @@
+/*
+Musical note values for time and rate options, resolved against the global --bpm.
+A note value is a fraction of a whole note, where a quarter note is one beat: "1/4" is a beat,
+"1/8" half a beat and "1/1" a bar of 4/4. A trailing "d" makes it dotted (1.5 times as long)
+and a trailing "t" a triplet (two thirds as long). As a time, a note value is its duration; as a
+rate, it is one cycle per note. Plain numbers keep the option's own unit (ms or Hz).
+Options are parsed in any order, so commands keep the parsed `Timing` and resolve it once the
+tempo is known.
+ */
+use audiofxrs::dsp::lfo::synced_frequency;
+
+/// A time or rate option, either in the option's own unit or as a note value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Timing {
+    Value(f32),
+    Beats(f32),
+}
+
+impl Timing {
+    /// Parses a plain number (with `unit` as an optional suffix) or a note value.
+    pub fn parse(value: &str, unit: &str) -> Option<Timing> {
+        if let Some((numerator, denominator)) = value.split_once('/') {
+            let (denominator, factor) = if let Some(denominator) = denominator.strip_suffix('d') {
+                (denominator, 1.5)
+            } else if let Some(denominator) = denominator.strip_suffix('t') {
+                (denominator, 2.0 / 3.0)
+            } else {
+                (denominator, 1.0)
+            };
+            let numerator: f32 = numerator.parse().ok()?;
+            let denominator: f32 = denominator.parse().ok()?;
+            (numerator > 0.0 && denominator > 0.0).then(|| Timing::Beats(4.0 * numerator / denominator * factor))
+        } else {
+            value.trim_end_matches(unit).parse().ok().map(Timing::Value)
+        }
+    }
+
+    /// The duration in milliseconds.
+    pub fn to_ms(self, bpm: Option<f32>) -> f32 {
+        match self {
+            Timing::Value(ms) => ms,
+            Timing::Beats(beats) => beats * 60_000.0 / require_bpm(bpm),
+        }
+    }
+
+    /// The rate in Hz, one cycle per note for note values.
+    pub fn to_hz(self, bpm: Option<f32>) -> f32 {
+        match self {
+            Timing::Value(hz) => hz,
+            Timing::Beats(beats) => synced_frequency(require_bpm(bpm), beats),
+        }
+    }
+}
+
+fn require_bpm(bpm: Option<f32>) -> f32 {
+    match bpm {
+        Some(bpm) => bpm,
+        None => {
+            eprintln!("Note values such as 1/8 need a tempo, given with --bpm");
+            std::process::exit(1);
+        }
+    }
+}